@@ -0,0 +1,68 @@
+// 比较 OpenCV BFMatcher 与纯 Rust 暴力匹配、LSH 的耗时
+// cargo bench --features native
+use std::time::{Duration, Instant};
+
+use opencv::{core::*, features2d::*, prelude::*};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use vo::feature::*;
+
+fn random_descriptors(rng: &mut StdRng, count: usize) -> Vec<Descriptor> {
+    (0..count)
+        .map(|_| {
+            let mut d = [0u8; 32];
+            rng.fill(&mut d);
+            d
+        })
+        .collect()
+}
+
+fn bench<F: FnMut()>(name: &str, iterations: u32, mut f: F) {
+    f();
+    let start = Instant::now();
+    for _ in 0..iterations {
+        f();
+    }
+    let elapsed: Duration = start.elapsed() / iterations;
+    println!("{:<32} {:>10.3} ms", name, elapsed.as_secs_f64() * 1000.0);
+}
+
+fn main() {
+    let mut rng = StdRng::seed_from_u64(0);
+
+    for &(query_count, train_count) in &[(500, 500), (1000, 1000), (2000, 10000)] {
+        println!("query {} / train {}", query_count, train_count);
+        let query = random_descriptors(&mut rng, query_count);
+        let train = random_descriptors(&mut rng, train_count);
+        let query_mat = descriptors_to_mat(&query);
+        let train_mat = descriptors_to_mat(&train);
+        let iterations = if train_count > 1000 { 3 } else { 10 };
+
+        let mut bf = BFMatcher::create(NORM_HAMMING, false).unwrap();
+        bench("opencv BFMatcher knn", iterations, || {
+            let mut knn_matches = opencv::core::Vector::<opencv::core::Vector<DMatch>>::new();
+            bf.knn_train_match(
+                &query_mat,
+                &train_mat,
+                &mut knn_matches,
+                2,
+                &no_array().unwrap(),
+                false,
+            )
+            .unwrap();
+        });
+
+        let hamming = HammingMatcher::new(&train);
+        bench("native brute-force knn", iterations, || {
+            hamming.knn_match(&query, 2);
+        });
+
+        bench("native lsh build", iterations, || {
+            LshIndex::new(LshConfig::default(), &train);
+        });
+
+        let lsh = LshIndex::new(LshConfig::default(), &train);
+        bench("native lsh knn", iterations, || {
+            lsh.knn_match(&query, 2);
+        });
+    }
+}
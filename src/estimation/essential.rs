@@ -0,0 +1,510 @@
+use nalgebra::*;
+
+use super::*;
+use crate::*;
+
+// 归一化相机坐标下的对应点，满足 x_1ᵀ·E·x_0 = 0
+pub type Correspondence = (Vector2<f64>, Vector2<f64>);
+
+// 三次及以下的 x y z 单项式，前 10 个为三次项，后 10 个为商环的基
+const MONOMIALS: [(u8, u8, u8); 20] = [
+    (3, 0, 0),
+    (2, 1, 0),
+    (2, 0, 1),
+    (1, 2, 0),
+    (1, 1, 1),
+    (1, 0, 2),
+    (0, 3, 0),
+    (0, 2, 1),
+    (0, 1, 2),
+    (0, 0, 3),
+    (2, 0, 0),
+    (1, 1, 0),
+    (1, 0, 1),
+    (0, 2, 0),
+    (0, 1, 1),
+    (0, 0, 2),
+    (1, 0, 0),
+    (0, 1, 0),
+    (0, 0, 1),
+    (0, 0, 0),
+];
+
+type Polynomial = [f64; 20];
+
+fn monomial_index(exponents: (u8, u8, u8)) -> usize {
+    MONOMIALS.iter().position(|m| *m == exponents).unwrap()
+}
+
+fn poly_mul(a: &Polynomial, b: &Polynomial) -> Polynomial {
+    let mut product = [0.0; 20];
+    for (i, ca) in a.iter().enumerate().filter(|(_, c)| **c != 0.0) {
+        for (j, cb) in b.iter().enumerate().filter(|(_, c)| **c != 0.0) {
+            let (ma, mb) = (MONOMIALS[i], MONOMIALS[j]);
+            let m = (ma.0 + mb.0, ma.1 + mb.1, ma.2 + mb.2);
+            // 约束均不超过三次
+            if m.0 + m.1 + m.2 <= 3 {
+                product[monomial_index(m)] += ca * cb;
+            }
+        }
+    }
+
+    product
+}
+
+fn poly_add(a: &Polynomial, b: &Polynomial, scale: f64) -> Polynomial {
+    let mut sum = *a;
+    for (s, c) in sum.iter_mut().zip(b.iter()) {
+        *s += scale * c;
+    }
+
+    sum
+}
+
+fn epipolar_row(x_0: &Vector2<f64>, x_1: &Vector2<f64>) -> [f64; 9] {
+    [
+        x_1.x * x_0.x,
+        x_1.x * x_0.y,
+        x_1.x,
+        x_1.y * x_0.x,
+        x_1.y * x_0.y,
+        x_1.y,
+        x_0.x,
+        x_0.y,
+        1.0,
+    ]
+}
+
+// 按特征值升序排列的 AᵀA 特征向量
+fn smallest_eigenvectors(rows: &[[f64; 9]], count: usize) -> Vec<VectorN<f64, U9>> {
+    let mut ata = MatrixN::<f64, U9>::zeros();
+    for row in rows {
+        let r = VectorN::<f64, U9>::from_row_slice(row);
+        ata += r * r.transpose();
+    }
+
+    let eigen = ata.symmetric_eigen();
+    let mut order = (0..9).collect::<Vec<usize>>();
+    order.sort_by(|a, b| {
+        eigen.eigenvalues[*a]
+            .partial_cmp(&eigen.eigenvalues[*b])
+            .unwrap()
+    });
+
+    order
+        .iter()
+        .take(count)
+        .map(|i| eigen.eigenvectors.column(*i).into_owned())
+        .collect()
+}
+
+fn to_matrix(e: &VectorN<f64, U9>) -> Matrix3<f64> {
+    Matrix3::from_row_slice(e.as_slice())
+}
+
+// Stewénius 的 Gröbner 基解法，返回至多 10 个本质矩阵
+pub fn five_point(samples: &[Correspondence]) -> Vec<Matrix3<f64>> {
+    if samples.len() < 5 {
+        return vec![];
+    }
+
+    let rows = samples
+        .iter()
+        .take(5)
+        .map(|(x_0, x_1)| epipolar_row(x_0, x_1))
+        .collect::<Vec<[f64; 9]>>();
+    // E = x·X + y·Y + z·Z + W
+    let basis = smallest_eigenvectors(&rows, 4);
+
+    let mut e = [[[0.0; 20]; 3]; 3];
+    for (k, entry) in e.iter_mut().flatten().enumerate() {
+        entry[monomial_index((1, 0, 0))] = basis[0][k];
+        entry[monomial_index((0, 1, 0))] = basis[1][k];
+        entry[monomial_index((0, 0, 1))] = basis[2][k];
+        entry[monomial_index((0, 0, 0))] = basis[3][k];
+    }
+
+    // det(E) = 0
+    let det = {
+        let minor = |r_0: usize, r_1: usize, c_0: usize, c_1: usize| {
+            poly_add(
+                &poly_mul(&e[r_0][c_0], &e[r_1][c_1]),
+                &poly_mul(&e[r_0][c_1], &e[r_1][c_0]),
+                -1.0,
+            )
+        };
+        let d = poly_mul(&e[0][0], &minor(1, 2, 1, 2));
+        let d = poly_add(&d, &poly_mul(&e[0][1], &minor(1, 2, 0, 2)), -1.0);
+        poly_add(&d, &poly_mul(&e[0][2], &minor(1, 2, 0, 1)), 1.0)
+    };
+
+    // 2·E·Eᵀ·E - tr(E·Eᵀ)·E = 0
+    let product = |a: &[[Polynomial; 3]; 3], b: &[[Polynomial; 3]; 3], transpose_b: bool| {
+        let mut m = [[[0.0; 20]; 3]; 3];
+        for (r, row) in m.iter_mut().enumerate() {
+            for (c, entry) in row.iter_mut().enumerate() {
+                *entry = (0..3).fold([0.0; 20], |sum, k| {
+                    let b_kc = if transpose_b { &b[c][k] } else { &b[k][c] };
+                    poly_add(&sum, &poly_mul(&a[r][k], b_kc), 1.0)
+                });
+            }
+        }
+        m
+    };
+    let eet = product(&e, &e, true);
+    let eete = product(&eet, &e, false);
+    let trace = poly_add(&poly_add(&eet[0][0], &eet[1][1], 1.0), &eet[2][2], 1.0);
+
+    let mut constraints = vec![det];
+    for (row, e_row) in eete.iter().zip(e.iter()) {
+        for (p, e_rc) in row.iter().zip(e_row.iter()) {
+            constraints.push(poly_add(
+                &poly_add(&[0.0; 20], p, 2.0),
+                &poly_mul(&trace, e_rc),
+                -1.0,
+            ));
+        }
+    }
+
+    // 高斯-约当消元，将三次项表示为基的线性组合
+    let mut a = MatrixMN::<f64, U10, U20>::from_fn(|i, j| constraints[i][j]);
+    for col in 0..10 {
+        let pivot = (col..10)
+            .max_by(|i, j| a[(*i, col)].abs().partial_cmp(&a[(*j, col)].abs()).unwrap())
+            .unwrap();
+        if a[(pivot, col)].abs() < 1e-12 {
+            return vec![];
+        }
+        a.swap_rows(col, pivot);
+        let p = a[(col, col)];
+        a.row_mut(col).scale_mut(1.0 / p);
+        for row in 0..10 {
+            if row != col {
+                let factor = a[(row, col)];
+                if factor != 0.0 {
+                    for k in col..20 {
+                        a[(row, k)] -= factor * a[(col, k)];
+                    }
+                }
+            }
+        }
+    }
+
+    // 乘以 x 的作用矩阵，x·b_i 用基表示
+    let mut action = MatrixN::<f64, U10>::zeros();
+    for i in 0..10 {
+        let (ex, ey, ez) = MONOMIALS[10 + i];
+        let target = monomial_index((ex + 1, ey, ez));
+        if target < 10 {
+            for j in 0..10 {
+                action[(i, j)] = -a[(target, 10 + j)];
+            }
+        } else {
+            action[(i, target - 10)] = 1.0;
+        }
+    }
+
+    let mut solutions = vec![];
+    for lambda in action.complex_eigenvalues().iter() {
+        if lambda.im.abs() > 1e-8 * (1.0 + lambda.re.abs()) {
+            continue;
+        }
+
+        let svd = (action - MatrixN::<f64, U10>::identity() * lambda.re).svd(false, true);
+        let v_t = match svd.v_t {
+            Some(v_t) => v_t,
+            None => continue,
+        };
+        let (min_index, _) = svd.singular_values.argmin();
+        let v = v_t.row(min_index);
+        if v[9].abs() < 1e-12 {
+            continue;
+        }
+
+        let (x, y, z) = (v[6] / v[9], v[7] / v[9], v[8] / v[9]);
+        let e = basis[0] * x + basis[1] * y + basis[2] * z + basis[3];
+        solutions.push(to_matrix(&(e / e.norm())));
+    }
+
+    solutions
+}
+
+// 奇异值按降序排列的 SVD，nalgebra 不保证顺序
+pub(super) fn sorted_svd(m: &Matrix3<f64>) -> Option<(Matrix3<f64>, Vector3<f64>, Matrix3<f64>)> {
+    let svd = m.svd(true, true);
+    let (u, v_t) = (svd.u?, svd.v_t?);
+    let mut order = [0, 1, 2];
+    order.sort_by(|a, b| {
+        svd.singular_values[*b]
+            .partial_cmp(&svd.singular_values[*a])
+            .unwrap()
+    });
+
+    Some((
+        Matrix3::from_columns(&[u.column(order[0]), u.column(order[1]), u.column(order[2])]),
+        Vector3::new(
+            svd.singular_values[order[0]],
+            svd.singular_values[order[1]],
+            svd.singular_values[order[2]],
+        ),
+        Matrix3::from_rows(&[v_t.row(order[0]), v_t.row(order[1]), v_t.row(order[2])]),
+    ))
+}
+
+// 线性八点法，并将奇异值投影为 (1, 1, 0)
+pub fn eight_point(samples: &[Correspondence]) -> Option<Matrix3<f64>> {
+    if samples.len() < 8 {
+        return None;
+    }
+
+    let rows = samples
+        .iter()
+        .map(|(x_0, x_1)| epipolar_row(x_0, x_1))
+        .collect::<Vec<[f64; 9]>>();
+    let e = to_matrix(&smallest_eigenvectors(&rows, 1)[0]);
+
+    let (u, _, v_t) = sorted_svd(&e)?;
+    Some(u * Matrix3::from_diagonal(&Vector3::new(1.0, 1.0, 0.0)) * v_t)
+}
+
+// 一阶几何误差，归一化坐标
+pub fn sampson_distance(e: &Matrix3<f64>, correspondence: &Correspondence) -> f64 {
+    let x_0 = correspondence.0.push(1.0);
+    let x_1 = correspondence.1.push(1.0);
+    let e_x_0 = e * x_0;
+    let e_t_x_1 = e.transpose() * x_1;
+    let denominator = e_x_0.x.powi(2) + e_x_0.y.powi(2) + e_t_x_1.x.powi(2) + e_t_x_1.y.powi(2);
+    if denominator > 0.0 {
+        x_1.dot(&e_x_0).abs() / denominator.sqrt()
+    } else {
+        f64::INFINITY
+    }
+}
+
+// 最小样本用五点法，内点多于 8 个时用八点法精化
+pub struct EssentialModel;
+
+impl RansacModel for EssentialModel {
+    type Data = Correspondence;
+    type Model = Matrix3<f64>;
+
+    fn get_sample_size(&self) -> usize {
+        5
+    }
+
+    fn fit(&self, samples: &[Correspondence]) -> Vec<Matrix3<f64>> {
+        five_point(samples)
+    }
+
+    fn residual(&self, model: &Matrix3<f64>, data: &Correspondence) -> f64 {
+        sampson_distance(model, data)
+    }
+
+    fn refine(&self, inliers: &[Correspondence], _model: &Matrix3<f64>) -> Option<Matrix3<f64>> {
+        eight_point(inliers)
+    }
+}
+
+// 两帧中的深度，失败时返回 None
+pub(super) fn triangulate_depths(
+    r: &Matrix3<f64>,
+    t: &Vector3<f64>,
+    correspondence: &Correspondence,
+) -> Option<(f64, f64)> {
+    // d_1·x_1 = d_0·R·x_0 + t
+    let a = Matrix3x2::from_columns(&[r * correspondence.0.push(1.0), -correspondence.1.push(1.0)]);
+    let ata = a.transpose() * a;
+    let d = ata.try_inverse()? * (a.transpose() * -t);
+    Some((d[0], d[1]))
+}
+
+// 分解本质矩阵并用正深度检验选出 X_1 = R·X_0 + t，返回通过检验的点
+pub fn recover_pose(
+    e: &Matrix3<f64>,
+    correspondences: &[Correspondence],
+    inliers: &[bool],
+) -> Option<(RnT, Vec<bool>)> {
+    let (mut u, _, mut v_t) = sorted_svd(e)?;
+    if u.determinant() < 0.0 {
+        u = -u;
+    }
+    if v_t.determinant() < 0.0 {
+        v_t = -v_t;
+    }
+
+    let w = Matrix3::new(0.0, -1.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0);
+    let t = u.column(2).into_owned();
+    let candidates = [
+        (u * w * v_t, t),
+        (u * w * v_t, -t),
+        (u * w.transpose() * v_t, t),
+        (u * w.transpose() * v_t, -t),
+    ];
+
+    candidates
+        .iter()
+        .map(|(r, t)| {
+            let valid = correspondences
+                .iter()
+                .zip(inliers.iter())
+                .map(|(c, inlier)| {
+                    *inlier
+                        && triangulate_depths(r, t, c)
+                            .map(|(d_0, d_1)| d_0 > 0.0 && d_1 > 0.0)
+                            .unwrap_or(false)
+                })
+                .collect::<Vec<bool>>();
+            (r, t, valid)
+        })
+        .max_by_key(|(_, _, valid)| valid.iter().filter(|v| **v).count())
+        .map(|(r, t, valid)| {
+            (
+                RnT {
+                    position_diff: *t,
+                    orientation_diff: *UnitQuaternion::from_matrix(r).quaternion(),
+                },
+                valid,
+            )
+        })
+}
+
+// 与 slove_transform 相同的输入输出，不依赖 OpenCV，阈值为像素
+pub fn slove_transform_native(
+    config: &RansacConfig,
+    camera_matrix: &Matrix3<f64>,
+    points_0: &[Vector2<f64>],
+    points_1: &[Vector2<f64>],
+) -> Result<TransformEstimate> {
+    if points_0.len() != points_1.len() || points_0.len() < 5 {
+        return Err(Error::from(ErrorKind::InvalidInput));
+    }
+
+    let k_inv = camera_matrix
+        .try_inverse()
+        .ok_or_else(|| Error::from(ErrorKind::InvalidInput))?;
+    let normalize = |p: &Vector2<f64>| {
+        let x = k_inv * p.push(1.0);
+        Vector2::new(x.x / x.z, x.y / x.z)
+    };
+    let correspondences = points_0
+        .iter()
+        .zip(points_1.iter())
+        .map(|(p_0, p_1)| (normalize(p_0), normalize(p_1)))
+        .collect::<Vec<Correspondence>>();
+
+    // 像素阈值换算到归一化坐标
+    let focal = 0.5 * (camera_matrix[(0, 0)] + camera_matrix[(1, 1)]);
+    let ransac = Ransac::new(
+        EssentialModel,
+        RansacConfig {
+            threshold: config.threshold / focal,
+            ..*config
+        },
+    );
+    let result = ransac
+        .run(&correspondences)
+        .ok_or_else(|| Error::from(ErrorKind::Other))?;
+    let (transform, cheirality) = recover_pose(&result.model, &correspondences, &result.inliers)
+        .ok_or_else(|| Error::from(ErrorKind::Other))?;
+
+    Ok(TransformEstimate {
+        transform,
+        residuals: correspondences
+            .iter()
+            .map(|c| sampson_distance(&result.model, c) * focal)
+            .collect(),
+        inliers_count: result.inliers_count,
+        inliers: result.inliers,
+        cheirality_count: cheirality.iter().filter(|v| **v).count(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::utils::test_scene::camera_matrix;
+
+    fn scene(
+        rng: &mut StdRng,
+        r: &Matrix3<f64>,
+        t: &Vector3<f64>,
+        count: usize,
+    ) -> Vec<Correspondence> {
+        (0..count)
+            .map(|_| {
+                let x_0 = Vector3::new(
+                    rng.gen_range(-4.0..4.0),
+                    rng.gen_range(-3.0..3.0),
+                    rng.gen_range(6.0..20.0),
+                );
+                let x_1 = r * x_0 + t;
+                (x_0.xy() / x_0.z, x_1.xy() / x_1.z)
+            })
+            .collect()
+    }
+
+    fn rotation_error(a: &Matrix3<f64>, b: &Quaternion<f64>) -> f64 {
+        UnitQuaternion::from_matrix(a).angle_to(&UnitQuaternion::from_quaternion(*b))
+    }
+
+    #[test]
+    fn test() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let r = *Rotation3::from_euler_angles(0.05, -0.1, 0.02).matrix();
+        let t = Vector3::new(0.6, -0.1, 0.3).normalize();
+        let e_true = t.cross_matrix() * r;
+
+        let data = scene(&mut rng, &r, &t, 5);
+        let solutions = five_point(&data);
+        assert!(!solutions.is_empty());
+        // 至少一个解与真值一致，且所有解满足约束
+        assert!(solutions.iter().any(|e| {
+            let e_true = e_true / e_true.norm();
+            (e - e_true).norm() < 1e-6 || (e + e_true).norm() < 1e-6
+        }));
+        for e in &solutions {
+            assert!(data.iter().all(|c| sampson_distance(e, c) < 1e-8));
+        }
+
+        let data = scene(&mut rng, &r, &t, 20);
+        let e = eight_point(&data).unwrap();
+        assert!(data.iter().all(|c| sampson_distance(&e, c) < 1e-8));
+
+        // 含 20% 外点
+        let k = camera_matrix();
+        let mut data = scene(&mut rng, &r, &t, 200);
+        for c in data.iter_mut().take(40) {
+            c.1 += Vector2::new(rng.gen_range(-0.2..0.2), rng.gen_range(-0.2..0.2));
+        }
+        let to_pixel = |x: &Vector2<f64>| (k * x.push(1.0)).xy();
+        let points_0 = data
+            .iter()
+            .map(|c| to_pixel(&c.0))
+            .collect::<Vec<Vector2<f64>>>();
+        let points_1 = data
+            .iter()
+            .map(|c| to_pixel(&c.1))
+            .collect::<Vec<Vector2<f64>>>();
+
+        let estimate =
+            slove_transform_native(&RansacConfig::default(), &k, &points_0, &points_1).unwrap();
+        assert!(estimate.inliers_count >= 160);
+        assert!(estimate.inliers.iter().skip(40).all(|inlier| *inlier));
+        assert!(estimate.cheirality_count >= 160);
+        assert!(rotation_error(&r, &estimate.transform.orientation_diff) < 1e-6);
+        assert!((estimate.transform.position_diff - t).norm() < 1e-6);
+
+        // 与 OpenCV 的结果一致
+        let reference = slove_transform(&k, &points_0, &points_1).unwrap();
+        assert!(
+            UnitQuaternion::from_quaternion(reference.transform.orientation_diff).angle_to(
+                &UnitQuaternion::from_quaternion(estimate.transform.orientation_diff)
+            ) < 1e-3
+        );
+        assert!(
+            (reference.transform.position_diff - estimate.transform.position_diff).norm() < 1e-3
+        );
+    }
+}
@@ -5,25 +5,372 @@ use crate::*;
 
 pub struct Estimator {
     camera_matrix: Matrix3<f64>,
+    config: EssentialConfig,
+    motion_config: MotionConfig,
+    pnp_config: PnpConfig,
+    // 连续 PnP 失败的帧数
+    failures: usize,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum PoseSource {
+    // 对已三角化的路标点求解 PnP
+    Pnp,
+    // 地图为空或 PnP 连续失败时由本质矩阵重新初始化
+    Initialization,
+}
+
+pub struct FrameEstimate {
+    pub frame_id: u64,
+    // 世界坐标到相机坐标的变换 X_c = R·X_w + t
+    pub pose: RnT,
+    pub source: PoseSource,
+    pub inliers_count: usize,
+    // 外点所属的轨迹 ID
+    pub outlier_track_ids: Vec<u64>,
 }
 
 impl Estimator {
     pub fn new(camera_matrix: Matrix3<f64>) -> Self {
-        Self { camera_matrix }
+        Self::with_config(camera_matrix, EssentialConfig::default())
+    }
+
+    pub fn with_config(camera_matrix: Matrix3<f64>, config: EssentialConfig) -> Self {
+        Self {
+            camera_matrix,
+            config,
+            motion_config: MotionConfig::default(),
+            pnp_config: PnpConfig::default(),
+            failures: 0,
+        }
+    }
+
+    pub fn set_motion_config(&mut self, motion_config: MotionConfig) {
+        self.motion_config = motion_config;
+    }
+
+    pub fn set_pnp_config(&mut self, pnp_config: PnpConfig) {
+        self.pnp_config = pnp_config;
     }
 
     pub fn test_slove_transform(&self, tracked: &track::Tracked) -> Result<RnT> {
+        // 与上一关键帧求解，未选取关键帧时固定间隔 3 帧
+        let reference = tracked.get_last_keyframe().unwrap_or(3);
+
+        self.solve_tracked(tracked, 0, reference)
+            .map(|(estimate, _)| estimate.transform)
+    }
+
+    // 用两帧共视的轨迹求解，返回值附带参与求解的轨迹 ID，与内点掩码一一对应
+    pub fn solve_tracked(
+        &self,
+        tracked: &track::Tracked,
+        frame_index_0: u32,
+        frame_index_1: u32,
+    ) -> Result<(TransformEstimate, Vec<u64>)> {
+        self.solve_motion_tracked(tracked, frame_index_0, frame_index_1)
+            .map(|(motion, track_ids)| (motion.estimate, track_ids))
+    }
+
+    // 同 solve_tracked，并给出自动选择的运动模型
+    pub fn solve_motion_tracked(
+        &self,
+        tracked: &track::Tracked,
+        frame_index_0: u32,
+        frame_index_1: u32,
+    ) -> Result<(MotionEstimate, Vec<u64>)> {
         let mut points_0 = vec![];
         let mut points_1 = vec![];
+        let mut track_ids = vec![];
         for i in 0..tracked.points_count() {
-            if let Some(p_0) = tracked.get_point(0, i) {
-                if let Some(p_1) = tracked.get_point(3, i) {
+            if let Some(p_0) = tracked.get_point(frame_index_0, i) {
+                if let Some(p_1) = tracked.get_point(frame_index_1, i) {
                     points_0.push(p_0.vp_position);
                     points_1.push(p_1.vp_position);
+                    track_ids.push(tracked.get_track_id(i).unwrap());
+                }
+            }
+        }
+
+        estimate_motion(
+            &self.motion_config,
+            &self.config,
+            &self.camera_matrix,
+            &points_0,
+            &points_1,
+        )
+        .map(|motion| (motion, track_ids))
+    }
+
+    // 逐对求解相邻帧并串联，返回各帧到第 0 帧的变换 X_0 = R·X_i + t
+    // 单目尺度不可观，每对相邻帧的平移均为单位长度，静止或纯旋转时为 0
+    // 某对相邻帧无法求解时，该帧及更早的帧为 None
+    pub fn estimate(&self, tracked: &track::Tracked) -> Vec<Option<RnT>> {
+        let mut poses = Vec::with_capacity(tracked.frames_count() as usize);
+        if tracked.frames_count() == 0 {
+            return poses;
+        }
+
+        let mut pose = Some(RnT {
+            position_diff: Vector3::zeros(),
+            orientation_diff: Quaternion::identity(),
+        });
+        poses.push(pose);
+        for frame_index in 1..tracked.frames_count() {
+            pose = pose.and_then(|pose| {
+                self.solve_pair(tracked, frame_index, frame_index - 1)
+                    .map(|rnt| chain(&pose, &rnt))
+            });
+            poses.push(pose);
+        }
+
+        poses
+    }
+
+    // 以 PnP 定位最新一帧，写入位姿后用新的观测更新地图
+    // 地图为空或 PnP 连续失败 max_failures 次时与参考帧求解本质矩阵重新初始化
+    // 此前的失败帧返回错误且不写入位姿，以保留已有地图的尺度
+    pub fn track_frame(
+        &mut self,
+        tracker: &track::Tracker,
+        map: &mut map::LocalMap,
+    ) -> Result<FrameEstimate> {
+        let tracked = tracker.get_tracked();
+        let frame_id = tracked
+            .get_frame_id(0)
+            .ok_or_else(|| Error::from(ErrorKind::InvalidInput))?;
+
+        let estimate = match self.localize(&tracked, map, frame_id) {
+            Ok(estimate) => estimate,
+            Err(error) => {
+                self.failures += 1;
+                if !map.is_empty() && self.failures < self.pnp_config.max_failures {
+                    return Err(error);
                 }
+                self.initialize(&tracked, map, frame_id)?
             }
+        };
+        self.failures = 0;
+        map.set_pose(frame_id, estimate.pose);
+        map.update(tracker);
+
+        Ok(estimate)
+    }
+
+    fn localize(
+        &self,
+        tracked: &track::Tracked,
+        map: &map::LocalMap,
+        frame_id: u64,
+    ) -> Result<FrameEstimate> {
+        let mut object_points = vec![];
+        let mut image_points = vec![];
+        let mut track_ids = vec![];
+        for i in 0..tracked.points_count() {
+            let track_id = tracked.get_track_id(i).unwrap();
+            if let (Some(landmark), Some(p)) = (map.get_landmark(track_id), tracked.get_point(0, i))
+            {
+                object_points.push(landmark.get_position());
+                image_points.push(p.vp_position);
+                track_ids.push(track_id);
+            }
+        }
+        if object_points.len() < self.pnp_config.min_inliers {
+            return Err(Error::from(ErrorKind::InvalidInput));
+        }
+
+        // 以上一帧的位姿为初值
+        let guess = tracked.get_frame_id(1).and_then(|id| map.get_pose(id));
+        let estimate = solve_pnp(
+            &self.pnp_config,
+            &self.camera_matrix,
+            &object_points,
+            &image_points,
+            guess,
+        )?;
+
+        Ok(FrameEstimate {
+            frame_id,
+            pose: estimate.pose,
+            source: PoseSource::Pnp,
+            inliers_count: estimate.inliers_count,
+            outlier_track_ids: outliers(&track_ids, &estimate.inliers),
+        })
+    }
+
+    // 与上一关键帧求解，未选取关键帧时取窗口内最早的帧
+    // 单目尺度不可观，平移取单位长度，尺度不一致的旧位姿与路标点随之丢弃
+    fn initialize(
+        &self,
+        tracked: &track::Tracked,
+        map: &mut map::LocalMap,
+        frame_id: u64,
+    ) -> Result<FrameEstimate> {
+        let reference = tracked
+            .get_last_keyframe()
+            .unwrap_or_else(|| tracked.frames_count().saturating_sub(1));
+        if reference == 0 {
+            return Err(Error::from(ErrorKind::InvalidInput));
+        }
+
+        let (motion, track_ids) = self.solve_motion_tracked(tracked, reference, 0)?;
+        // 静止或纯旋转时无法三角化，平面场景的单应分解有歧义时已返回错误
+        if motion.kind != MotionKind::General && motion.kind != MotionKind::Planar {
+            return Err(Error::from(ErrorKind::Other));
+        }
+
+        let reference_id = tracked.get_frame_id(reference).unwrap();
+        let reference_pose = map.get_pose(reference_id).copied().unwrap_or(RnT {
+            position_diff: Vector3::zeros(),
+            orientation_diff: Quaternion::identity(),
+        });
+        map.clear();
+        map.set_pose(reference_id, reference_pose);
+
+        Ok(FrameEstimate {
+            frame_id,
+            pose: chain(&motion.estimate.transform, &reference_pose),
+            source: PoseSource::Initialization,
+            inliers_count: motion.estimate.inliers_count,
+            outlier_track_ids: outliers(&track_ids, &motion.estimate.inliers),
+        })
+    }
+
+    // 求 prev 帧到 cur 帧的变换
+    fn solve_pair(&self, tracked: &track::Tracked, prev: u32, cur: u32) -> Option<RnT> {
+        self.solve_tracked(tracked, prev, cur)
+            .map(|(estimate, _)| estimate.transform)
+            .ok()
+    }
+}
+
+fn outliers(track_ids: &[u64], inliers: &[bool]) -> Vec<u64> {
+    track_ids
+        .iter()
+        .zip(inliers.iter())
+        .filter(|(_, inlier)| !**inlier)
+        .map(|(track_id, _)| *track_id)
+        .collect()
+}
+
+// a 为 cur 帧到第 0 帧的变换，b 为 prev 帧到 cur 帧的变换
+fn chain(a: &RnT, b: &RnT) -> RnT {
+    let r_a = UnitQuaternion::from_quaternion(a.orientation_diff);
+    let r_b = UnitQuaternion::from_quaternion(b.orientation_diff);
+
+    RnT {
+        position_diff: r_a * b.position_diff + a.position_diff,
+        orientation_diff: *(r_a * r_b).quaternion(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::SystemTime;
+
+    use super::*;
+    use crate::utils::test_scene::*;
+
+    // 相机光心位于 center 时的观测，第 0 帧之后逐一延续上一帧的同序号点
+    fn observe(
+        landmarks: &[Vector3<f64>],
+        center: Vector3<f64>,
+        frame: usize,
+    ) -> Vec<feature::MatchedFeature> {
+        project_points(&camera_matrix(), landmarks, &Matrix3::identity(), &-center)
+            .into_iter()
+            .enumerate()
+            .map(|(i, position)| feature::MatchedFeature {
+                prev_index: if frame > 0 { i as u32 } else { u32::MAX },
+                prev_frame_offset: 1,
+                position,
+                match_degree: if frame > 0 { 1.0 } else { 0.0 },
+                depth: None,
+                descriptor: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test() {
+        let estimator = Estimator::new(camera_matrix());
+        let mut tracker = track::Tracker::new(16);
+        let time = SystemTime::now();
+
+        // 相机每帧沿 z 轴前进 0.5
+        let landmarks = grid_landmarks(100, 10, 1.0, |i| 10.0 + (i % 7) as f64);
+        let project = |frame: usize| {
+            observe(
+                &landmarks,
+                Vector3::new(0.0, 0.0, frame as f64 * 0.5),
+                frame,
+            )
+        };
+
+        tracker.update_matched(&time, &project(0));
+        assert_eq!(estimator.estimate(&tracker.get_tracked()).len(), 1);
+
+        for frame in 1..4 {
+            tracker.update_matched(&time, &project(frame));
         }
+        let poses = estimator.estimate(&tracker.get_tracked());
+        assert_eq!(poses.len(), 4);
+        for (i, pose) in poses.iter().enumerate() {
+            let pose = pose.unwrap();
+            // 较早的帧位于第 0 帧的后方
+            assert!((pose.position_diff.z + i as f64).abs() < 1e-2);
+            assert!(UnitQuaternion::from_quaternion(pose.orientation_diff).angle() < 1e-3);
+        }
+
+        let (estimate, track_ids) = estimator
+            .solve_tracked(&tracker.get_tracked(), 1, 0)
+            .unwrap();
+        assert_eq!(track_ids.len(), estimate.inliers.len());
+        assert_eq!(estimate.inliers_count, 100);
+    }
+
+    #[test]
+    fn test_track_frame() {
+        let mut estimator = Estimator::new(camera_matrix());
+        let mut map = map::LocalMap::new(camera_matrix());
+        let mut tracker = track::Tracker::new(16);
+        let time = SystemTime::now();
+
+        // 相机每帧沿 x 轴平移 0.3，第 3 帧的观测被打乱
+        let landmarks = grid_landmarks(100, 10, 1.0, |i| 5.0 + (i * 7 % 11) as f64 * 0.5);
+        let scrambled = (0..landmarks.len())
+            .map(|i| landmarks[i * 37 % landmarks.len()])
+            .collect::<Vec<Vector3<f64>>>();
+        let project = |frame: usize| {
+            let landmarks = if frame == 3 { &scrambled } else { &landmarks };
+            observe(landmarks, Vector3::new(frame as f64 * 0.3, 0.0, 0.0), frame)
+        };
 
-        slove_transform(&self.camera_matrix, &points_0, &points_1)
+        tracker.update_matched(&time, &project(0));
+        assert!(estimator.track_frame(&tracker, &mut map).is_err());
+
+        for frame in 1..6 {
+            tracker.update_matched(&time, &project(frame));
+            // 单次 PnP 失败不会重新初始化
+            if frame == 3 {
+                assert!(estimator.track_frame(&tracker, &mut map).is_err());
+                assert!(map.get_pose(tracker.get_frame_id().unwrap()).is_none());
+                continue;
+            }
+            let estimate = estimator.track_frame(&tracker, &mut map).unwrap();
+            let source = if frame == 1 {
+                PoseSource::Initialization
+            } else {
+                PoseSource::Pnp
+            };
+            assert_eq!(estimate.source, source);
+            assert!(estimate.outlier_track_ids.is_empty());
+
+            // 初始化的平移为单位长度，之后的帧包括失败后的帧沿用同一尺度
+            let r = UnitQuaternion::from_quaternion(estimate.pose.orientation_diff);
+            let center = -(r.inverse() * estimate.pose.position_diff);
+            assert!((center - Vector3::new(frame as f64, 0.0, 0.0)).norm() < 1e-2);
+        }
+        assert!(!map.is_empty());
     }
 }
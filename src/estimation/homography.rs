@@ -0,0 +1,138 @@
+use nalgebra::*;
+
+use super::*;
+
+// 像素坐标下的对应点，x_1 ~ H·x_0
+pub type PointPair = (Vector2<f64>, Vector2<f64>);
+
+pub struct HomographyModel;
+
+// 平移到质心并缩放到平均距离 √2
+fn normalization(points: &[Vector2<f64>]) -> Matrix3<f64> {
+    let centroid = points.iter().fold(Vector2::zeros(), |sum, p| sum + p) / points.len() as f64;
+    let mean_distance =
+        points.iter().map(|p| (p - centroid).norm()).sum::<f64>() / points.len() as f64;
+    let s = if mean_distance > 0.0 {
+        2f64.sqrt() / mean_distance
+    } else {
+        1.0
+    };
+
+    Matrix3::new(
+        s,
+        0.0,
+        -s * centroid.x,
+        0.0,
+        s,
+        -s * centroid.y,
+        0.0,
+        0.0,
+        1.0,
+    )
+}
+
+// 归一化 DLT，至少 4 对点
+pub fn homography_dlt(pairs: &[PointPair]) -> Option<Matrix3<f64>> {
+    if pairs.len() < 4 {
+        return None;
+    }
+
+    let t_0 = normalization(&pairs.iter().map(|p| p.0).collect::<Vec<Vector2<f64>>>());
+    let t_1 = normalization(&pairs.iter().map(|p| p.1).collect::<Vec<Vector2<f64>>>());
+
+    let mut ata = MatrixN::<f64, U9>::zeros();
+    for (p_0, p_1) in pairs {
+        let x = t_0 * p_0.push(1.0);
+        let y = t_1 * p_1.push(1.0);
+        let rows = [
+            [
+                0.0,
+                0.0,
+                0.0,
+                -x.x,
+                -x.y,
+                -x.z,
+                y.y * x.x,
+                y.y * x.y,
+                y.y * x.z,
+            ],
+            [
+                x.x,
+                x.y,
+                x.z,
+                0.0,
+                0.0,
+                0.0,
+                -y.x * x.x,
+                -y.x * x.y,
+                -y.x * x.z,
+            ],
+        ];
+        for row in rows.iter() {
+            let r = VectorN::<f64, U9>::from_row_slice(row);
+            ata += r * r.transpose();
+        }
+    }
+
+    let eigen = ata.symmetric_eigen();
+    let (min_index, _) = eigen.eigenvalues.argmin();
+    let h = Matrix3::from_row_slice(eigen.eigenvectors.column(min_index).as_slice());
+    let h = t_1.try_inverse()? * h * t_0;
+    if h[(2, 2)].abs() < f64::EPSILON {
+        return None;
+    }
+
+    Some(h / h[(2, 2)])
+}
+
+impl RansacModel for HomographyModel {
+    type Data = PointPair;
+    type Model = Matrix3<f64>;
+
+    fn get_sample_size(&self) -> usize {
+        4
+    }
+
+    fn fit(&self, samples: &[PointPair]) -> Vec<Matrix3<f64>> {
+        homography_dlt(samples).into_iter().collect()
+    }
+
+    // 正向转移误差
+    fn residual(&self, model: &Matrix3<f64>, data: &PointPair) -> f64 {
+        let x = model * data.0.push(1.0);
+        if x.z.abs() < f64::EPSILON {
+            return f64::INFINITY;
+        }
+        (x.xy() / x.z - data.1).norm()
+    }
+
+    fn refine(&self, inliers: &[PointPair], _model: &Matrix3<f64>) -> Option<Matrix3<f64>> {
+        homography_dlt(inliers)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let h = Matrix3::new(1.1, 0.05, 12.0, -0.03, 0.95, -7.0, 1e-4, -2e-4, 1.0);
+        let mut pairs = (0..100)
+            .map(|i| {
+                let p = Vector2::new((i % 10) as f64 * 40.0, (i / 10) as f64 * 30.0);
+                let q = h * p.push(1.0);
+                (p, q.xy() / q.z)
+            })
+            .collect::<Vec<PointPair>>();
+        for pair in pairs.iter_mut().take(25) {
+            pair.1 += Vector2::new(30.0, -20.0);
+        }
+
+        let result = Ransac::new(HomographyModel, RansacConfig::default())
+            .run(&pairs)
+            .unwrap();
+        assert_eq!(result.inliers_count, 75);
+        assert!((result.model - h).norm() < 1e-6);
+    }
+}
@@ -1,5 +1,15 @@
+mod essential;
 mod estimator;
+mod homography;
+mod motion;
+mod pnp;
+mod ransac;
 mod slover;
 
+pub use essential::*;
 pub use estimator::*;
+pub use homography::*;
+pub use motion::*;
+pub use pnp::*;
+pub use ransac::*;
 pub use slover::*;
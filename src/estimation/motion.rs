@@ -0,0 +1,381 @@
+use nalgebra::*;
+use opencv::{calib3d::*, core::Mat, types::*};
+
+use super::*;
+use crate::utils::*;
+use crate::{Error, ErrorKind, Result, RnT};
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum MotionKind {
+    // 视差过小，视为静止
+    Stationary,
+    // 单应近似为旋转，平移不可观
+    PureRotation,
+    // 单应分解得到的平面场景运动
+    Planar,
+    // 本质矩阵求得的一般运动
+    General,
+}
+
+#[derive(Copy, Clone)]
+pub struct MotionConfig {
+    // 对应点像素位移中值低于该值时视为静止
+    pub stationary_parallax: f64,
+    // S_H / (S_H + S_E) 超过该值时选用单应，同 ORB-SLAM
+    pub homography_ratio: f64,
+    // 归一化单应奇异值的最大差异，低于该值时视为纯旋转
+    pub rotation_tolerance: f64,
+    // 单应 RANSAC 的像素阈值
+    pub homography_threshold: f64,
+    // 单应分解所需的视差角中值，弧度
+    pub min_parallax: f64,
+    pub seed: u64,
+}
+
+pub struct MotionEstimate {
+    pub kind: MotionKind,
+    pub estimate: TransformEstimate,
+    pub homography_score_ratio: f64,
+}
+
+impl Default for MotionConfig {
+    fn default() -> Self {
+        Self {
+            stationary_parallax: 1.0,
+            homography_ratio: 0.45,
+            rotation_tolerance: 0.01,
+            homography_threshold: 2.0,
+            min_parallax: 1.0f64.to_radians(),
+            seed: 0,
+        }
+    }
+}
+
+// 卡方检验阈值，自由度分别为 2 和 1，σ 取 1 像素
+const CHI_2_H: f64 = 5.99;
+const CHI_2_E: f64 = 3.84;
+// 次优分解的正深度点数不低于最优的该比例时无法区分，同 ORB-SLAM
+const AMBIGUITY_RATIO: f64 = 0.75;
+
+fn model_score(residuals: &[f64], inliers: &[bool], chi_2: f64) -> f64 {
+    residuals
+        .iter()
+        .zip(inliers.iter())
+        .map(|(r, inlier)| {
+            let r_2 = r * r;
+            if *inlier && r_2 < chi_2 {
+                CHI_2_H - r_2
+            } else {
+                0.0
+            }
+        })
+        .sum()
+}
+
+fn identity() -> RnT {
+    RnT {
+        position_diff: Vector3::zeros(),
+        orientation_diff: Quaternion::identity(),
+    }
+}
+
+// 同时拟合单应与本质矩阵，按得分比选择模型，并单独报告静止与纯旋转
+pub fn estimate_motion(
+    config: &MotionConfig,
+    essential_config: &EssentialConfig,
+    camera_matrix: &Matrix3<f64>,
+    points_0: &[Vector2<f64>],
+    points_1: &[Vector2<f64>],
+) -> Result<MotionEstimate> {
+    if points_0.len() != points_1.len() || points_0.len() < 5 {
+        return Err(Error::from(ErrorKind::InvalidInput));
+    }
+
+    let mut displacements = points_0
+        .iter()
+        .zip(points_1.iter())
+        .map(|(p_0, p_1)| (p_1 - p_0).norm())
+        .collect::<Vec<f64>>();
+    let residuals = displacements.clone();
+    displacements.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    if displacements[displacements.len() / 2] < config.stationary_parallax {
+        let inliers = residuals
+            .iter()
+            .map(|d| *d < config.homography_threshold)
+            .collect::<Vec<bool>>();
+        return Ok(MotionEstimate {
+            kind: MotionKind::Stationary,
+            estimate: TransformEstimate {
+                transform: identity(),
+                inliers_count: inliers.iter().filter(|inlier| **inlier).count(),
+                inliers,
+                cheirality_count: 0,
+                residuals,
+            },
+            homography_score_ratio: 1.0,
+        });
+    }
+
+    let pairs = points_0
+        .iter()
+        .cloned()
+        .zip(points_1.iter().cloned())
+        .collect::<Vec<PointPair>>();
+    let homography = Ransac::new(
+        HomographyModel,
+        RansacConfig {
+            threshold: config.homography_threshold,
+            seed: config.seed,
+            ..RansacConfig::default()
+        },
+    )
+    .run(&pairs);
+    let essential =
+        slove_transform_with_config(essential_config, camera_matrix, points_0, points_1).ok();
+
+    let (homography, h_residuals, s_h) = match homography {
+        Some(h) => {
+            let residuals = pairs
+                .iter()
+                .map(|pair| HomographyModel.residual(&h.model, pair))
+                .collect::<Vec<f64>>();
+            let score = model_score(&residuals, &h.inliers, CHI_2_H);
+            (Some(h), residuals, score)
+        }
+        None => (None, vec![], 0.0),
+    };
+    let s_e = essential
+        .as_ref()
+        .map(|e| model_score(&e.residuals, &e.inliers, CHI_2_E))
+        .unwrap_or(0.0);
+    let ratio = if s_h + s_e > 0.0 {
+        s_h / (s_h + s_e)
+    } else {
+        0.0
+    };
+
+    let homography = match homography {
+        Some(h) if ratio > config.homography_ratio || essential.is_none() => h,
+        _ => {
+            return essential
+                .map(|estimate| MotionEstimate {
+                    kind: MotionKind::General,
+                    estimate,
+                    homography_score_ratio: ratio,
+                })
+                .ok_or_else(|| Error::from(ErrorKind::Other))
+        }
+    };
+
+    let k_inv = camera_matrix
+        .try_inverse()
+        .ok_or_else(|| Error::from(ErrorKind::InvalidInput))?;
+    let h_n = k_inv * homography.model * camera_matrix;
+    let (u, s, v_t) = sorted_svd(&h_n).ok_or_else(|| Error::from(ErrorKind::Other))?;
+
+    // 奇异值均相等时 H = K·R·K⁻¹
+    if (s[0] - s[2]) / s[1] < config.rotation_tolerance {
+        let mut r = u * v_t;
+        if r.determinant() < 0.0 {
+            r = -r;
+        }
+        return Ok(MotionEstimate {
+            kind: MotionKind::PureRotation,
+            estimate: TransformEstimate {
+                transform: RnT {
+                    position_diff: Vector3::zeros(),
+                    orientation_diff: *UnitQuaternion::from_matrix(&r).quaternion(),
+                },
+                inliers: homography.inliers,
+                inliers_count: homography.inliers_count,
+                cheirality_count: 0,
+                residuals: h_residuals,
+            },
+            homography_score_ratio: ratio,
+        });
+    }
+
+    let (transform, cheirality) = decompose_homography(
+        config,
+        camera_matrix,
+        &homography.model,
+        &pairs,
+        &homography.inliers,
+    )?;
+    Ok(MotionEstimate {
+        kind: MotionKind::Planar,
+        estimate: TransformEstimate {
+            transform,
+            inliers: homography.inliers,
+            inliers_count: homography.inliers_count,
+            cheirality_count: cheirality,
+            residuals: h_residuals,
+        },
+        homography_score_ratio: ratio,
+    })
+}
+
+// 取使最多内点位于两相机前方的分解，平移归一化为单位长度
+// 次优分解同样可行、最优分解未覆盖绝大多数内点或视差不足时返回错误
+fn decompose_homography(
+    config: &MotionConfig,
+    camera_matrix: &Matrix3<f64>,
+    homography: &Matrix3<f64>,
+    pairs: &[PointPair],
+    inliers: &[bool],
+) -> Result<(RnT, usize)> {
+    let mut rotations = VectorOfMat::new();
+    let mut translations = VectorOfMat::new();
+    let mut normals = VectorOfMat::new();
+    decompose_homography_mat(
+        &matrix_to_mat(homography),
+        &matrix_to_mat(camera_matrix),
+        &mut rotations,
+        &mut translations,
+        &mut normals,
+    )
+    .map_err(|_| Error::from(ErrorKind::Other))?;
+
+    let k_inv = camera_matrix
+        .try_inverse()
+        .ok_or_else(|| Error::from(ErrorKind::InvalidInput))?;
+    let normalize = |p: &Vector2<f64>| {
+        let x = k_inv * p.push(1.0);
+        Vector2::new(x.x / x.z, x.y / x.z)
+    };
+    let correspondences = pairs
+        .iter()
+        .zip(inliers.iter())
+        .filter(|(_, inlier)| **inlier)
+        .map(|(pair, _)| (normalize(&pair.0), normalize(&pair.1)))
+        .collect::<Vec<Correspondence>>();
+
+    // 各分解位于两相机前方的点数及这些点的视差角
+    let mut candidates = rotations
+        .iter()
+        .zip(translations.iter())
+        .map(|(r, t): (Mat, Mat)| {
+            let r = mat_to_matrix3(&r);
+            let t = mat_to_vector3(&t);
+            let center_1 = -(r.transpose() * t);
+            let parallaxes = correspondences
+                .iter()
+                .filter_map(|c| {
+                    let (d_0, d_1) = triangulate_depths(&r, &t, c)?;
+                    if d_0 <= 0.0 || d_1 <= 0.0 {
+                        return None;
+                    }
+                    let x = c.0.push(1.0) * d_0;
+                    let cos = x.normalize().dot(&(x - center_1).normalize());
+                    Some(cos.min(1.0).acos())
+                })
+                .collect::<Vec<f64>>();
+            (r, t, parallaxes)
+        })
+        .collect::<Vec<(Matrix3<f64>, Vector3<f64>, Vec<f64>)>>();
+    candidates.sort_by(|a, b| b.2.len().cmp(&a.2.len()));
+
+    let (r, t, mut parallaxes) = match candidates.len() {
+        0 => return Err(Error::from(ErrorKind::Other)),
+        1 => candidates.swap_remove(0),
+        _ => {
+            let second = candidates[1].2.len();
+            let best = candidates.swap_remove(0);
+            if second as f64 >= AMBIGUITY_RATIO * best.2.len() as f64 {
+                return Err(Error::from(ErrorKind::Other));
+            }
+            best
+        }
+    };
+    if parallaxes.is_empty() || (parallaxes.len() as f64) < 0.9 * correspondences.len() as f64 {
+        return Err(Error::from(ErrorKind::Other));
+    }
+    parallaxes.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    if parallaxes[parallaxes.len() / 2] < config.min_parallax {
+        return Err(Error::from(ErrorKind::Other));
+    }
+
+    Ok((
+        RnT {
+            position_diff: t.try_normalize(f64::EPSILON).unwrap_or(t),
+            orientation_diff: *UnitQuaternion::from_matrix(&r).quaternion(),
+        },
+        parallaxes.len(),
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::test_scene::{camera_matrix, grid_landmarks, project_points as project};
+
+    #[test]
+    fn test() {
+        let k = camera_matrix();
+        let config = MotionConfig::default();
+        let essential_config = EssentialConfig {
+            seed: Some(0),
+            ..EssentialConfig::default()
+        };
+        let identity = Matrix3::identity();
+        let r = *Rotation3::from_euler_angles(0.02, 0.08, -0.01).matrix();
+
+        let general = grid_landmarks(100, 10, 1.0, |i| 8.0 + (i * 7 % 11) as f64);
+        let planar = grid_landmarks(100, 10, 1.0, |_| 10.0);
+
+        let solve = |landmarks: &[Vector3<f64>], r: &Matrix3<f64>, t: &Vector3<f64>| {
+            estimate_motion(
+                &config,
+                &essential_config,
+                &k,
+                &project(&k, landmarks, &identity, &Vector3::zeros()),
+                &project(&k, landmarks, r, t),
+            )
+            .unwrap()
+        };
+
+        let points_0 = project(&k, &general, &identity, &Vector3::zeros());
+        let stationary =
+            estimate_motion(&config, &essential_config, &k, &points_0, &points_0).unwrap();
+        assert_eq!(stationary.kind, MotionKind::Stationary);
+
+        let rotation = solve(&general, &r, &Vector3::zeros());
+        assert_eq!(rotation.kind, MotionKind::PureRotation);
+        assert!(
+            UnitQuaternion::from_matrix(&r).angle_to(&UnitQuaternion::from_quaternion(
+                rotation.estimate.transform.orientation_diff
+            )) < 1e-6
+        );
+
+        // 平面只占视野中央时，次优分解也使 90% 的点位于相机前方，无法区分
+        let t = Vector3::new(0.8, 0.0, 0.2);
+        assert!(estimate_motion(
+            &config,
+            &essential_config,
+            &k,
+            &project(&k, &planar, &identity, &Vector3::zeros()),
+            &project(&k, &planar, &r, &t),
+        )
+        .is_err());
+
+        // 平面覆盖更大视野时分解唯一，与真值一致
+        let wide_planar = grid_landmarks(100, 10, 2.0, |_| 10.0);
+        let plane = solve(&wide_planar, &r, &t);
+        assert_eq!(plane.kind, MotionKind::Planar);
+        assert_eq!(
+            plane.estimate.cheirality_count,
+            plane.estimate.inliers_count
+        );
+        assert!(
+            UnitQuaternion::from_matrix(&r).angle_to(&UnitQuaternion::from_quaternion(
+                plane.estimate.transform.orientation_diff
+            )) < 1e-6
+        );
+        assert!((plane.estimate.transform.position_diff - t.normalize()).norm() < 1e-4);
+
+        let motion = solve(&general, &r, &t);
+        assert_eq!(motion.kind, MotionKind::General);
+        assert!(motion.homography_score_ratio < config.homography_ratio);
+        assert!((motion.estimate.transform.position_diff - t.normalize()).norm() < 1e-2);
+    }
+}
@@ -0,0 +1,206 @@
+use nalgebra::*;
+use opencv::{calib3d::*, core::*, types::*};
+
+use crate::utils::{mat_to_matrix3, mat_to_vector3, matrix_to_mat, ConfigFile};
+use crate::*;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum PnpMethod {
+    Iterative,
+    Epnp,
+    P3p,
+    Ap3p,
+}
+
+#[derive(Copy, Clone)]
+pub struct PnpConfig {
+    pub method: PnpMethod,
+    pub iterations: i32,
+    // 内点的重投影误差阈值，像素
+    pub reprojection_error: f64,
+    pub confidence: f64,
+    // 内点数低于该值时视为跟踪失败
+    pub min_inliers: usize,
+    // 连续跟踪失败该次数后重新初始化
+    pub max_failures: usize,
+}
+
+pub struct PnpEstimate {
+    // 世界坐标到相机坐标的变换 X_c = R·X_w + t
+    pub pose: RnT,
+    // 与输入点一一对应，true 为 RANSAC 内点
+    pub inliers: Vec<bool>,
+    pub inliers_count: usize,
+}
+
+impl PnpMethod {
+    fn to_flag(self) -> i32 {
+        match self {
+            PnpMethod::Iterative => SOLVEPNP_ITERATIVE,
+            PnpMethod::Epnp => SOLVEPNP_EPNP,
+            PnpMethod::P3p => SOLVEPNP_P3P,
+            PnpMethod::Ap3p => SOLVEPNP_AP3P,
+        }
+    }
+}
+
+impl PnpConfig {
+    pub fn from_config_file(config: &ConfigFile) -> Result<Self> {
+        let default = Self::default();
+
+        Ok(Self {
+            method: match config.get::<String>("pnp.method")? {
+                Some(method) => match method.as_str() {
+                    "iterative" => PnpMethod::Iterative,
+                    "epnp" => PnpMethod::Epnp,
+                    "p3p" => PnpMethod::P3p,
+                    "ap3p" => PnpMethod::Ap3p,
+                    _ => return Err(Error::from(ErrorKind::InvalidData)),
+                },
+                None => default.method,
+            },
+            iterations: config.get_or("pnp.iterations", default.iterations)?,
+            reprojection_error: config
+                .get_or("pnp.reprojection_error", default.reprojection_error)?,
+            confidence: config.get_or("pnp.confidence", default.confidence)?,
+            min_inliers: config.get_or("pnp.min_inliers", default.min_inliers)?,
+            max_failures: config.get_or("pnp.max_failures", default.max_failures)?,
+        })
+    }
+}
+
+impl Default for PnpConfig {
+    fn default() -> Self {
+        Self {
+            method: PnpMethod::Epnp,
+            iterations: 100,
+            reprojection_error: 4.0,
+            confidence: 0.99,
+            min_inliers: 15,
+            max_failures: 3,
+        }
+    }
+}
+
+// 由 2D-3D 对应求解相机位姿，guess 仅在迭代法中作为初值
+pub fn solve_pnp(
+    config: &PnpConfig,
+    camera_matrix: &Matrix3<f64>,
+    object_points: &[Vector3<f64>],
+    image_points: &[Vector2<f64>],
+    guess: Option<&RnT>,
+) -> Result<PnpEstimate> {
+    if object_points.len() != image_points.len() || object_points.len() < 4 {
+        return Err(Error::from(ErrorKind::InvalidInput));
+    }
+
+    let object_points = object_points
+        .iter()
+        .map(|p| Point3d::new(p.x, p.y, p.z))
+        .collect::<VectorOfPoint3d>();
+    let image_points = image_points
+        .iter()
+        .map(|p| Point2d::new(p.x, p.y))
+        .collect::<VectorOfPoint2d>();
+
+    let use_guess = guess.is_some() && config.method == PnpMethod::Iterative;
+    let (mut rvec, mut tvec) = match guess.filter(|_| use_guess) {
+        Some(guess) => {
+            let r = UnitQuaternion::from_quaternion(guess.orientation_diff)
+                .to_rotation_matrix()
+                .into_inner();
+            let mut rvec = Mat::default().unwrap();
+            rodrigues(&matrix_to_mat(&r), &mut rvec, &mut Mat::default().unwrap())
+                .map_err(|_| Error::from(ErrorKind::Other))?;
+            (rvec, matrix_to_mat(&guess.position_diff))
+        }
+        None => (Mat::default().unwrap(), Mat::default().unwrap()),
+    };
+
+    let mut inlier_indices = VectorOfi32::new();
+    let solved = solve_pnp_ransac(
+        &object_points,
+        &image_points,
+        &matrix_to_mat(camera_matrix),
+        &Mat::default().unwrap(),
+        &mut rvec,
+        &mut tvec,
+        use_guess,
+        config.iterations,
+        config.reprojection_error as f32,
+        config.confidence,
+        &mut inlier_indices,
+        config.method.to_flag(),
+    )
+    .map_err(|_| Error::from(ErrorKind::Other))?;
+    if !solved || inlier_indices.len() < config.min_inliers {
+        return Err(Error::from(ErrorKind::Other));
+    }
+
+    let mut r = Mat::default().unwrap();
+    rodrigues(&rvec, &mut r, &mut Mat::default().unwrap())
+        .map_err(|_| Error::from(ErrorKind::Other))?;
+
+    let mut inliers = vec![false; object_points.len()];
+    for i in inlier_indices.iter() {
+        inliers[i as usize] = true;
+    }
+
+    Ok(PnpEstimate {
+        pose: RnT {
+            position_diff: mat_to_vector3(&tvec),
+            orientation_diff: *UnitQuaternion::from_matrix(&mat_to_matrix3(&r)).quaternion(),
+        },
+        inliers_count: inlier_indices.len(),
+        inliers,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::test_scene::*;
+
+    #[test]
+    fn test() {
+        let camera_matrix = camera_matrix();
+        let object_points = grid_landmarks(50, 10, 1.0, |i| 8.0 + (i % 7) as f64);
+        let rotation = UnitQuaternion::from_euler_angles(0.02, -0.05, 0.01);
+        let translation = Vector3::new(0.3, -0.1, 0.5);
+        let mut image_points = project_points(
+            &camera_matrix,
+            &object_points,
+            rotation.to_rotation_matrix().matrix(),
+            &translation,
+        );
+        // 前 5 个点为外点
+        for p in image_points.iter_mut().take(5) {
+            p.x += 50.0;
+        }
+
+        for method in [PnpMethod::Epnp, PnpMethod::Iterative].iter() {
+            let config = PnpConfig {
+                method: *method,
+                ..PnpConfig::default()
+            };
+            let estimate =
+                solve_pnp(&config, &camera_matrix, &object_points, &image_points, None).unwrap();
+            assert_eq!(estimate.inliers_count, 45);
+            assert!(estimate.inliers.iter().skip(5).all(|inlier| *inlier));
+            assert!((estimate.pose.position_diff - translation).norm() < 1e-3);
+            assert!(
+                UnitQuaternion::from_quaternion(estimate.pose.orientation_diff).angle_to(&rotation)
+                    < 1e-4
+            );
+        }
+
+        assert!(solve_pnp(
+            &PnpConfig::default(),
+            &camera_matrix,
+            &object_points[..3],
+            &image_points[..3],
+            None
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,212 @@
+use rand::{rngs::StdRng, seq::index, SeedableRng};
+
+// RANSAC 所需的最小模型接口，本质矩阵、单应和 PnP 等模型均可实现
+pub trait RansacModel {
+    type Data: Clone;
+    type Model: Clone;
+
+    // 最小样本数
+    fn get_sample_size(&self) -> usize;
+
+    // 由最小样本求解，可能有多个解
+    fn fit(&self, samples: &[Self::Data]) -> Vec<Self::Model>;
+
+    // 单个数据相对模型的误差，与阈值同一单位
+    fn residual(&self, model: &Self::Model, data: &Self::Data) -> f64;
+
+    // 用全部内点重新估计，默认不精化
+    fn refine(&self, _inliers: &[Self::Data], _model: &Self::Model) -> Option<Self::Model> {
+        None
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct RansacConfig {
+    pub threshold: f64,
+    pub confidence: f64,
+    pub max_iterations: usize,
+    pub seed: u64,
+}
+
+pub struct Ransac<M: RansacModel> {
+    model: M,
+    config: RansacConfig,
+}
+
+pub struct RansacResult<T> {
+    pub model: T,
+    pub inliers: Vec<bool>,
+    pub inliers_count: usize,
+    pub iterations: usize,
+}
+
+impl Default for RansacConfig {
+    fn default() -> Self {
+        Self {
+            threshold: 1.0,
+            confidence: 0.999,
+            max_iterations: 1000,
+            seed: 0,
+        }
+    }
+}
+
+impl<M: RansacModel> Ransac<M> {
+    pub fn new(model: M, config: RansacConfig) -> Self {
+        Self { model, config }
+    }
+
+    pub fn get_model(&self) -> &M {
+        &self.model
+    }
+
+    pub fn run(&self, data: &[M::Data]) -> Option<RansacResult<M::Model>> {
+        let sample_size = self.model.get_sample_size();
+        if sample_size == 0 || data.len() < sample_size {
+            return None;
+        }
+
+        let mut rng = StdRng::seed_from_u64(self.config.seed);
+        let mut best: Option<(M::Model, Vec<bool>, usize, f64)> = None;
+        let mut max_iterations = self.config.max_iterations;
+        let mut iterations = 0;
+        while iterations < max_iterations {
+            iterations += 1;
+
+            let samples = index::sample(&mut rng, data.len(), sample_size)
+                .iter()
+                .map(|i| data[i].clone())
+                .collect::<Vec<M::Data>>();
+            for model in self.model.fit(&samples) {
+                let (inliers, count, cost) = self.evaluate(&model, data);
+                if best
+                    .as_ref()
+                    .map(|(_, _, best_count, best_cost)| {
+                        count > *best_count || (count == *best_count && cost < *best_cost)
+                    })
+                    .unwrap_or(true)
+                {
+                    max_iterations = max_iterations.min(
+                        self.adaptive_iterations(count as f64 / data.len() as f64, sample_size),
+                    );
+                    best = Some((model, inliers, count, cost));
+                }
+            }
+        }
+
+        let (mut model, mut inliers, mut count, cost) = best?;
+        let inlier_data = data
+            .iter()
+            .zip(inliers.iter())
+            .filter(|(_, inlier)| **inlier)
+            .map(|(d, _)| d.clone())
+            .collect::<Vec<M::Data>>();
+        if let Some(refined) = self.model.refine(&inlier_data, &model) {
+            let (refined_inliers, refined_count, refined_cost) = self.evaluate(&refined, data);
+            if refined_count > count || (refined_count == count && refined_cost <= cost) {
+                model = refined;
+                inliers = refined_inliers;
+                count = refined_count;
+            }
+        }
+
+        Some(RansacResult {
+            model,
+            inliers,
+            inliers_count: count,
+            iterations,
+        })
+    }
+
+    // MSAC 代价，内点按误差平方计，外点按阈值平方计
+    fn evaluate(&self, model: &M::Model, data: &[M::Data]) -> (Vec<bool>, usize, f64) {
+        let threshold_2 = self.config.threshold * self.config.threshold;
+        let mut cost = 0.0;
+        let inliers = data
+            .iter()
+            .map(|d| {
+                let r = self.model.residual(model, d);
+                let r_2 = r * r;
+                if r_2 < threshold_2 {
+                    cost += r_2;
+                    true
+                } else {
+                    cost += threshold_2;
+                    false
+                }
+            })
+            .collect::<Vec<bool>>();
+        let count = inliers.iter().filter(|inlier| **inlier).count();
+
+        (inliers, count, cost)
+    }
+
+    // 以当前内点率达到置信度所需的迭代次数
+    fn adaptive_iterations(&self, inlier_ratio: f64, sample_size: usize) -> usize {
+        let p_good = inlier_ratio.powi(sample_size as i32);
+        if p_good <= f64::EPSILON {
+            return self.config.max_iterations;
+        }
+        if p_good >= 1.0 - f64::EPSILON {
+            return 1;
+        }
+
+        let iterations = (1.0 - self.config.confidence).ln() / (1.0 - p_good).ln();
+        if iterations.is_finite() {
+            iterations.ceil().max(1.0) as usize
+        } else {
+            self.config.max_iterations
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // 拟合 y = a·x + b
+    struct LineModel;
+
+    impl RansacModel for LineModel {
+        type Data = (f64, f64);
+        type Model = (f64, f64);
+
+        fn get_sample_size(&self) -> usize {
+            2
+        }
+
+        fn fit(&self, samples: &[(f64, f64)]) -> Vec<(f64, f64)> {
+            let (x_0, y_0) = samples[0];
+            let (x_1, y_1) = samples[1];
+            if (x_1 - x_0).abs() < 1e-9 {
+                return vec![];
+            }
+            let a = (y_1 - y_0) / (x_1 - x_0);
+            vec![(a, y_0 - a * x_0)]
+        }
+
+        fn residual(&self, model: &(f64, f64), data: &(f64, f64)) -> f64 {
+            (model.0 * data.0 + model.1 - data.1).abs()
+        }
+    }
+
+    #[test]
+    fn test() {
+        let mut data = (0..80)
+            .map(|i| (i as f64, 2.0 * i as f64 + 1.0))
+            .collect::<Vec<(f64, f64)>>();
+        data.extend((0..20).map(|i| (i as f64, 100.0 - 3.0 * i as f64)));
+
+        let ransac = Ransac::new(LineModel, RansacConfig::default());
+        let result = ransac.run(&data).unwrap();
+        assert!((result.model.0 - 2.0).abs() < 1e-9);
+        assert!((result.model.1 - 1.0).abs() < 1e-9);
+        assert_eq!(result.inliers_count, 80);
+        assert!(result.iterations < 100);
+
+        // 相同种子结果一致
+        let again = ransac.run(&data).unwrap();
+        assert_eq!(again.iterations, result.iterations);
+        assert!(ransac.run(&data[..1]).is_none());
+    }
+}
@@ -2,13 +2,132 @@ use nalgebra::*;
 use opencv::{calib3d::*, core::*, types::*};
 
 use super::*;
+use crate::utils::{mat_to_matrix3, ConfigFile};
 use crate::*;
 
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum EssentialMethod {
+    Ransac,
+    Lmeds,
+    UsacDefault,
+    UsacFast,
+    UsacAccurate,
+    UsacProsac,
+    UsacMagsac,
+    // 不依赖 OpenCV 的 slove_transform_native
+    Native,
+}
+
+#[derive(Copy, Clone)]
+pub struct EssentialConfig {
+    pub method: EssentialMethod,
+    pub confidence: f64,
+    // 内点的像素距离阈值，LMEDS 不使用
+    pub threshold: f64,
+    // 仅作用于 Native 方法，为 None 时取 0；
+    // OpenCV 的 RANSAC、LMEDS 与 USAC 内部使用固定种子，结果本身可复现
+    pub seed: Option<u64>,
+}
+
+pub struct TransformEstimate {
+    pub transform: RnT,
+    // 与输入点一一对应，true 为 RANSAC 内点
+    pub inliers: Vec<bool>,
+    pub inliers_count: usize,
+    // 内点中三角化后位于两相机前方的点数
+    pub cheirality_count: usize,
+    // 各点相对本质矩阵的 Sampson 距离，像素
+    pub residuals: Vec<f64>,
+}
+
+impl EssentialMethod {
+    fn to_flag(self) -> Option<i32> {
+        match self {
+            EssentialMethod::Ransac => Some(RANSAC),
+            EssentialMethod::Lmeds => Some(LMEDS),
+            EssentialMethod::UsacDefault => Some(USAC_DEFAULT),
+            EssentialMethod::UsacFast => Some(USAC_FAST),
+            EssentialMethod::UsacAccurate => Some(USAC_ACCURATE),
+            EssentialMethod::UsacProsac => Some(USAC_PROSAC),
+            EssentialMethod::UsacMagsac => Some(USAC_MAGSAC),
+            EssentialMethod::Native => None,
+        }
+    }
+}
+
+impl EssentialConfig {
+    pub fn from_config_file(config: &ConfigFile) -> Result<Self> {
+        let default = Self::default();
+
+        Ok(Self {
+            method: match config.get::<String>("essential.method")? {
+                Some(method) => match method.as_str() {
+                    "ransac" => EssentialMethod::Ransac,
+                    "lmeds" => EssentialMethod::Lmeds,
+                    "usac_default" => EssentialMethod::UsacDefault,
+                    "usac_fast" => EssentialMethod::UsacFast,
+                    "usac_accurate" => EssentialMethod::UsacAccurate,
+                    "usac_prosac" => EssentialMethod::UsacProsac,
+                    "usac_magsac" => EssentialMethod::UsacMagsac,
+                    "native" => EssentialMethod::Native,
+                    _ => return Err(Error::from(ErrorKind::InvalidData)),
+                },
+                None => default.method,
+            },
+            confidence: config.get_or("essential.confidence", default.confidence)?,
+            threshold: config.get_or("essential.threshold", default.threshold)?,
+            seed: config.get("essential.seed")?,
+        })
+    }
+}
+
+impl Default for EssentialConfig {
+    fn default() -> Self {
+        Self {
+            method: EssentialMethod::Ransac,
+            confidence: 0.999,
+            threshold: 1.0,
+            seed: None,
+        }
+    }
+}
+
 pub fn slove_transform(
     camera_matrix: &Matrix3<f64>,
     points_0: &[Vector2<f64>],
     points_1: &[Vector2<f64>],
-) -> Result<RnT> {
+) -> Result<TransformEstimate> {
+    slove_transform_with_config(
+        &EssentialConfig::default(),
+        camera_matrix,
+        points_0,
+        points_1,
+    )
+}
+
+pub fn slove_transform_with_config(
+    config: &EssentialConfig,
+    camera_matrix: &Matrix3<f64>,
+    points_0: &[Vector2<f64>],
+    points_1: &[Vector2<f64>],
+) -> Result<TransformEstimate> {
+    let method = match config.method.to_flag() {
+        Some(method) => method,
+        None => {
+            return slove_transform_native(
+                &RansacConfig {
+                    threshold: config.threshold,
+                    confidence: config.confidence,
+                    seed: config.seed.unwrap_or(0),
+                    ..RansacConfig::default()
+                },
+                camera_matrix,
+                points_0,
+                points_1,
+            )
+        }
+    };
+
     if points_0.len() == points_1.len() {
         if points_0.len() >= 5 {
             let trans_points = |points: &[Vector2<f64>]| {
@@ -35,47 +154,67 @@ pub fn slove_transform(
                 mat
             };
 
+            let mut mask = Mat::default().unwrap();
             return find_essential_mat_matrix(
                 &points_0,
                 &points_1,
                 &cam_mat,
-                RANSAC,
-                0.999,
-                1.0,
-                &mut no_array().unwrap(),
+                method,
+                config.confidence,
+                config.threshold,
+                &mut mask,
             )
             .map_err(|_| Error::from(ErrorKind::Other))
             .and_then(|e| {
+                // 多解时 E 为 3n×3，只取第一个
+                if e.rows() < 3 {
+                    return Err(Error::from(ErrorKind::Other));
+                }
+                let inliers = (0..points_0.len())
+                    .map(|i| *mask.at::<u8>(i as i32).unwrap() != 0)
+                    .collect::<Vec<bool>>();
+                let residuals =
+                    sampson_distances(camera_matrix, &mat_to_matrix3(&e), &points_0, &points_1);
+
                 let mut r = Mat::default().unwrap();
                 let mut t = Mat::default().unwrap();
+                // recoverPose 只使用掩码中的内点，并写回通过正深度检验的点
+                let mut cheirality_mask = Mat::default().unwrap();
+                mask.copy_to(&mut cheirality_mask).unwrap();
 
                 recover_pose_camera(
-                    &e,
+                    &e.row_range(&Range::new(0, 3).unwrap()).unwrap(),
                     &points_0,
                     &points_1,
                     &cam_mat,
                     &mut r,
                     &mut t,
-                    &mut no_array().unwrap(),
+                    &mut cheirality_mask,
                 )
-                .map(|_| RnT {
-                    position_diff: Vector3::new(
-                        *t.at(0).unwrap(),
-                        *t.at(1).unwrap(),
-                        *t.at(2).unwrap(),
-                    ),
-                    orientation_diff: *UnitQuaternion::from_matrix(&Matrix3::new(
-                        *r.at_2d(0, 0).unwrap(),
-                        *r.at_2d(0, 1).unwrap(),
-                        *r.at_2d(0, 2).unwrap(),
-                        *r.at_2d(1, 0).unwrap(),
-                        *r.at_2d(1, 1).unwrap(),
-                        *r.at_2d(1, 2).unwrap(),
-                        *r.at_2d(2, 0).unwrap(),
-                        *r.at_2d(2, 1).unwrap(),
-                        *r.at_2d(2, 2).unwrap(),
-                    ))
-                    .quaternion(),
+                .map(|cheirality_count| TransformEstimate {
+                    transform: RnT {
+                        position_diff: Vector3::new(
+                            *t.at(0).unwrap(),
+                            *t.at(1).unwrap(),
+                            *t.at(2).unwrap(),
+                        ),
+                        orientation_diff: *UnitQuaternion::from_matrix(&Matrix3::new(
+                            *r.at_2d(0, 0).unwrap(),
+                            *r.at_2d(0, 1).unwrap(),
+                            *r.at_2d(0, 2).unwrap(),
+                            *r.at_2d(1, 0).unwrap(),
+                            *r.at_2d(1, 1).unwrap(),
+                            *r.at_2d(1, 2).unwrap(),
+                            *r.at_2d(2, 0).unwrap(),
+                            *r.at_2d(2, 1).unwrap(),
+                            *r.at_2d(2, 2).unwrap(),
+                        ))
+                        .quaternion(),
+                    },
+                    inliers_count: inliers.iter().filter(|inlier| **inlier).count(),
+                    inliers,
+                    cheirality_count: cheirality_count.max(0) as usize,
+                    residuals,
                 })
                 .map_err(|_| Error::from(ErrorKind::Other))
             });
@@ -85,8 +224,106 @@ pub fn slove_transform(
     Err(Error::from(ErrorKind::InvalidInput))
 }
 
+// x_1ᵀ·F·x_0 的一阶近似几何距离
+fn sampson_distances(
+    camera_matrix: &Matrix3<f64>,
+    e: &Matrix3<f64>,
+    points_0: &VectorOfPoint2d,
+    points_1: &VectorOfPoint2d,
+) -> Vec<f64> {
+    let k_inv = camera_matrix
+        .try_inverse()
+        .unwrap_or_else(Matrix3::identity);
+    let f = k_inv.transpose() * e * k_inv;
+
+    points_0
+        .iter()
+        .zip(points_1.iter())
+        .map(|(p_0, p_1)| {
+            let x_0 = Vector3::new(p_0.x, p_0.y, 1.0);
+            let x_1 = Vector3::new(p_1.x, p_1.y, 1.0);
+            let f_x_0 = f * x_0;
+            let f_t_x_1 = f.transpose() * x_1;
+            let denominator =
+                f_x_0.x.powi(2) + f_x_0.y.powi(2) + f_t_x_1.x.powi(2) + f_t_x_1.y.powi(2);
+            if denominator > 0.0 {
+                x_1.dot(&f_x_0).abs() / denominator.sqrt()
+            } else {
+                0.0
+            }
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod test {
+    use super::*;
+    use crate::utils::test_scene::*;
+
     #[test]
-    fn test() {}
+    fn test() {
+        let camera_matrix = camera_matrix();
+        let landmarks = grid_landmarks(60, 10, 1.0, |i| 8.0 + (i % 7) as f64);
+        let project = |offset: Vector3<f64>| {
+            project_points(&camera_matrix, &landmarks, &Matrix3::identity(), &-offset)
+        };
+
+        let points_0 = project(Vector3::zeros());
+        let mut points_1 = project(Vector3::new(0.5, 0.0, 0.2));
+        // 前 5 个点为外点
+        for p in points_1.iter_mut().take(5) {
+            p.y += 40.0;
+        }
+
+        let estimate = slove_transform(&camera_matrix, &points_0, &points_1).unwrap();
+        assert_eq!(estimate.inliers.len(), 60);
+        assert!(estimate.inliers.iter().take(5).all(|inlier| !inlier));
+        assert_eq!(estimate.inliers_count, 55);
+        assert_eq!(estimate.cheirality_count, 55);
+        assert!(estimate.residuals[0] > 1.0);
+        assert!(estimate.residuals[10] < 1e-3);
+        assert!(slove_transform(&camera_matrix, &points_0[..4], &points_1[..4]).is_err());
+
+        // 重复求解的结果一致，Native 方法使用相同种子
+        for method in &[
+            EssentialMethod::Ransac,
+            EssentialMethod::Lmeds,
+            EssentialMethod::UsacMagsac,
+            EssentialMethod::Native,
+        ] {
+            let config = EssentialConfig {
+                method: *method,
+                seed: Some(7),
+                ..EssentialConfig::default()
+            };
+            let estimate_0 =
+                slove_transform_with_config(&config, &camera_matrix, &points_0, &points_1).unwrap();
+            let estimate_1 =
+                slove_transform_with_config(&config, &camera_matrix, &points_0, &points_1).unwrap();
+            assert_eq!(estimate_0.inliers, estimate_1.inliers);
+            assert_eq!(estimate_0.residuals, estimate_1.residuals);
+            assert!(estimate_0.inliers.iter().take(5).all(|inlier| !inlier));
+        }
+
+        // Native 方法换用其他种子仍能剔除外点
+        let estimate = slove_transform_with_config(
+            &EssentialConfig {
+                method: EssentialMethod::Native,
+                seed: Some(8),
+                ..EssentialConfig::default()
+            },
+            &camera_matrix,
+            &points_0,
+            &points_1,
+        )
+        .unwrap();
+        assert_eq!(estimate.inliers_count, 55);
+
+        let config = EssentialConfig::from_config_file(
+            &ConfigFile::parse("essential.method = usac_magsac\nessential.seed = 1\n").unwrap(),
+        )
+        .unwrap();
+        assert_eq!(config.method, EssentialMethod::UsacMagsac);
+        assert_eq!(config.seed, Some(1));
+    }
 }
@@ -0,0 +1,333 @@
+use std::cmp::Ordering;
+
+// SIFT 只取 features2d 中的实现，xfeatures2d 只用到 BRIEF
+use opencv::{core::*, features2d::*, xfeatures2d::BriefDescriptorExtractor};
+
+use super::*;
+use crate::{Error, ErrorKind, Result};
+
+pub trait FeatureExtractor {
+    // 描述子的距离类型，NORM_HAMMING 或 NORM_L2
+    fn get_descriptor_norm(&self) -> i32;
+
+    fn detect(&mut self, src: &Mat, mask: Option<&Mat>) -> Result<Vector<KeyPoint>>;
+
+    fn compute(&mut self, src: &Mat, keypoints: &mut Vector<KeyPoint>) -> Result<Mat>;
+
+    // 能否为其他检测器得到的特征点计算描述子，如网格 FAST
+    fn accepts_external_keypoints(&self) -> bool {
+        true
+    }
+
+    // 特征点所在金字塔层相对原图的缩小倍数，层的约定未知时为 None
+    fn get_octave_scale(&self, octave: i32) -> Option<f64> {
+        if octave == 0 {
+            Some(1.0)
+        } else {
+            None
+        }
+    }
+}
+
+pub struct OrbExtractor {
+    orb: Ptr<dyn ORB>,
+    wta_k: i32,
+    scale_factor: f32,
+}
+
+pub struct AkazeExtractor {
+    akaze: Ptr<dyn AKAZE>,
+    features_count: i32,
+}
+
+pub struct BriskExtractor {
+    brisk: Ptr<dyn BRISK>,
+}
+
+pub struct SiftExtractor {
+    sift: Ptr<dyn SIFT>,
+}
+
+pub struct GfttBriefExtractor {
+    gftt: Ptr<dyn GFTTDetector>,
+    brief: Ptr<dyn BriefDescriptorExtractor>,
+}
+
+pub(super) fn detect_with<T: Feature2DTrait>(
+    detector: &mut T,
+    src: &Mat,
+    mask: Option<&Mat>,
+) -> Result<Vector<KeyPoint>> {
+    let mut keypoints = Vector::<KeyPoint>::new();
+    match mask {
+        Some(mask) => detector.detect(src, &mut keypoints, mask),
+        None => detector.detect(src, &mut keypoints, &no_array().unwrap()),
+    }
+    .map(|_| keypoints)
+    .map_err(|_| Error::from(ErrorKind::Other))
+}
+
+fn compute_with<T: Feature2DTrait>(
+    descriptor_extractor: &mut T,
+    src: &Mat,
+    keypoints: &mut Vector<KeyPoint>,
+) -> Result<Mat> {
+    let mut descriptors = Mat::default().unwrap();
+    descriptor_extractor
+        .compute(src, keypoints, &mut descriptors)
+        .map(|_| descriptors)
+        .map_err(|_| Error::from(ErrorKind::Other))
+}
+
+impl OrbExtractor {
+    pub fn new(config: &ExtractorConfig) -> Self {
+        Self {
+            orb: ORB::create(
+                config.features_count,
+                config.scale_factor,
+                config.levels_count,
+                config.edge_threshold,
+                0,
+                config.wta_k,
+                config.score_type,
+                config.patch_size,
+                config.fast_threshold,
+            )
+            .unwrap(),
+            wta_k: config.wta_k,
+            scale_factor: config.scale_factor,
+        }
+    }
+}
+
+impl FeatureExtractor for OrbExtractor {
+    fn get_descriptor_norm(&self) -> i32 {
+        // WTA_K 为 3 或 4 时每个比较结果占 2 位
+        if self.wta_k > 2 {
+            NORM_HAMMING2
+        } else {
+            NORM_HAMMING
+        }
+    }
+
+    fn detect(&mut self, src: &Mat, mask: Option<&Mat>) -> Result<Vector<KeyPoint>> {
+        detect_with(&mut self.orb, src, mask)
+    }
+
+    fn compute(&mut self, src: &Mat, keypoints: &mut Vector<KeyPoint>) -> Result<Mat> {
+        compute_with(&mut self.orb, src, keypoints)
+    }
+
+    fn get_octave_scale(&self, octave: i32) -> Option<f64> {
+        Some((self.scale_factor as f64).powi(octave))
+    }
+}
+
+// 按响应保留最强的 count 个特征点，count 不大于 0 时不限制
+fn retain_best(keypoints: Vector<KeyPoint>, count: i32) -> Vector<KeyPoint> {
+    if count <= 0 || keypoints.len() <= count as usize {
+        return keypoints;
+    }
+
+    let mut sorted = keypoints.iter().collect::<Vec<KeyPoint>>();
+    sorted.sort_by(|a, b| {
+        b.response
+            .partial_cmp(&a.response)
+            .unwrap_or(Ordering::Equal)
+    });
+    sorted.truncate(count as usize);
+    Vector::<KeyPoint>::from_iter(sorted.into_iter())
+}
+
+impl AkazeExtractor {
+    pub fn new(config: &ExtractorConfig) -> Self {
+        Self {
+            akaze: AKAZE::create(
+                AKAZE_DescriptorType::DESCRIPTOR_MLDB,
+                0,
+                3,
+                0.001,
+                // AKAZE 每个 octave 尺度加倍，层数过多时顶层过小
+                config.levels_count.max(1).min(4),
+                4,
+                KAZE_DiffusivityType::DIFF_PM_G2,
+            )
+            .unwrap(),
+            features_count: config.features_count,
+        }
+    }
+}
+
+impl FeatureExtractor for AkazeExtractor {
+    fn get_descriptor_norm(&self) -> i32 {
+        NORM_HAMMING
+    }
+
+    // 描述子依赖检测时写入 class_id 的尺度层
+    fn accepts_external_keypoints(&self) -> bool {
+        false
+    }
+
+    // AKAZE 没有数量上限参数，检测后截断
+    fn detect(&mut self, src: &Mat, mask: Option<&Mat>) -> Result<Vector<KeyPoint>> {
+        detect_with(&mut self.akaze, src, mask)
+            .map(|keypoints| retain_best(keypoints, self.features_count))
+    }
+
+    fn compute(&mut self, src: &Mat, keypoints: &mut Vector<KeyPoint>) -> Result<Mat> {
+        compute_with(&mut self.akaze, src, keypoints)
+    }
+}
+
+impl BriskExtractor {
+    pub fn new(config: &ExtractorConfig) -> Self {
+        Self {
+            brisk: BRISK::create(
+                config.fast_threshold.min(255),
+                config.levels_count.min(8),
+                1.0,
+            )
+            .unwrap(),
+        }
+    }
+}
+
+impl FeatureExtractor for BriskExtractor {
+    fn get_descriptor_norm(&self) -> i32 {
+        NORM_HAMMING
+    }
+
+    fn detect(&mut self, src: &Mat, mask: Option<&Mat>) -> Result<Vector<KeyPoint>> {
+        detect_with(&mut self.brisk, src, mask)
+    }
+
+    fn compute(&mut self, src: &Mat, keypoints: &mut Vector<KeyPoint>) -> Result<Mat> {
+        compute_with(&mut self.brisk, src, keypoints)
+    }
+}
+
+impl SiftExtractor {
+    pub fn new(config: &ExtractorConfig) -> Self {
+        Self {
+            sift: SIFT::create(config.features_count, 3, 0.04, 10.0, 1.6).unwrap(),
+        }
+    }
+}
+
+impl FeatureExtractor for SiftExtractor {
+    fn get_descriptor_norm(&self) -> i32 {
+        NORM_L2
+    }
+
+    // 描述子依赖检测时编码在 octave 中的尺度层
+    fn accepts_external_keypoints(&self) -> bool {
+        false
+    }
+
+    fn detect(&mut self, src: &Mat, mask: Option<&Mat>) -> Result<Vector<KeyPoint>> {
+        detect_with(&mut self.sift, src, mask)
+    }
+
+    fn compute(&mut self, src: &Mat, keypoints: &mut Vector<KeyPoint>) -> Result<Mat> {
+        compute_with(&mut self.sift, src, keypoints)
+    }
+}
+
+impl GfttBriefExtractor {
+    pub fn new(config: &ExtractorConfig) -> Self {
+        Self {
+            gftt: GFTTDetector::create(
+                config.features_count,
+                0.01,
+                config.edge_threshold as f64 / 4.0,
+                3,
+                false,
+                0.04,
+            )
+            .unwrap(),
+            brief: BriefDescriptorExtractor::create(32, false).unwrap(),
+        }
+    }
+}
+
+impl FeatureExtractor for GfttBriefExtractor {
+    fn get_descriptor_norm(&self) -> i32 {
+        NORM_HAMMING
+    }
+
+    fn detect(&mut self, src: &Mat, mask: Option<&Mat>) -> Result<Vector<KeyPoint>> {
+        detect_with(&mut self.gftt, src, mask)
+    }
+
+    fn compute(&mut self, src: &Mat, keypoints: &mut Vector<KeyPoint>) -> Result<Mat> {
+        compute_with(&mut self.brief, src, keypoints)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let config = ExtractorConfig::default();
+        assert_eq!(
+            OrbExtractor::new(&config).get_descriptor_norm(),
+            NORM_HAMMING
+        );
+        assert_eq!(
+            AkazeExtractor::new(&config).get_descriptor_norm(),
+            NORM_HAMMING
+        );
+        assert_eq!(
+            BriskExtractor::new(&config).get_descriptor_norm(),
+            NORM_HAMMING
+        );
+        assert_eq!(SiftExtractor::new(&config).get_descriptor_norm(), NORM_L2);
+        assert_eq!(
+            GfttBriefExtractor::new(&config).get_descriptor_norm(),
+            NORM_HAMMING
+        );
+    }
+
+    #[test]
+    fn test_akaze_features_count() {
+        let mut img =
+            Mat::new_rows_cols_with_default(240, 320, CV_8U, opencv::core::Scalar::all(0.0))
+                .unwrap();
+        for y in 0..240 {
+            for x in 0..320 {
+                if (x / 16 + y / 16) % 2 == 0 {
+                    *img.at_2d_mut::<u8>(y, x).unwrap() = 255;
+                }
+            }
+        }
+
+        let unlimited = AkazeExtractor::new(&ExtractorConfig {
+            features_count: 0,
+            ..ExtractorConfig::default()
+        })
+        .detect(&img, None)
+        .unwrap();
+        assert!(unlimited.len() > 20);
+
+        let mut akaze = AkazeExtractor::new(&ExtractorConfig {
+            features_count: 20,
+            ..ExtractorConfig::default()
+        });
+        let keypoints = akaze.detect(&img, None).unwrap();
+        assert_eq!(keypoints.len(), 20);
+        let min_response = keypoints
+            .iter()
+            .map(|kp| kp.response)
+            .fold(f32::MAX, f32::min);
+        // 保留的是响应最强的点
+        assert!(
+            unlimited
+                .iter()
+                .filter(|kp| kp.response > min_response)
+                .count()
+                < 20
+        );
+    }
+}
@@ -1,37 +1,292 @@
-use opencv::{core::*, features2d::*};
+use std::cmp::Ordering;
+
+use opencv::{core::*, features2d::*, imgproc::*};
+
+use super::*;
+use crate::utils::ConfigFile;
+use crate::{Error, ErrorKind, Result};
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ExtractorBackend {
+    Orb,
+    Akaze,
+    Brisk,
+    Sift,
+    GfttBrief,
+    #[cfg(feature = "native")]
+    NativeOrb,
+}
+
+pub struct ExtractorConfig {
+    pub backend: ExtractorBackend,
+    pub features_count: i32,
+    pub scale_factor: f32,
+    pub levels_count: i32,
+    pub edge_threshold: i32,
+    pub wta_k: i32,
+    pub score_type: ORB_ScoreType,
+    pub patch_size: i32,
+    pub fast_threshold: i32,
+    // 不为 None 时按网格分块检测，使特征点分布均匀
+    pub grid: Option<GridConfig>,
+    // 不为 None 时用 cornerSubPix 将特征点位置精化到亚像素
+    pub subpixel: Option<SubpixelConfig>,
+}
+
+#[derive(Copy, Clone)]
+pub struct SubpixelConfig {
+    pub window_radius: i32,
+    pub max_iterations: i32,
+    pub epsilon: f64,
+}
 
 pub struct Extractor {
-    orb: Ptr<dyn ORB>,
+    backend: Box<dyn FeatureExtractor>,
+    grid_detector: Option<GridDetector>,
+    subpixel: Option<SubpixelConfig>,
+    mask: Option<Mat>,
 }
 
 pub struct Features {
     pub keypoints: Vector<KeyPoint>,
     pub descriptors: Mat,
+    pub descriptor_norm: i32,
+}
+
+impl ExtractorConfig {
+    pub fn from_config_file(config: &ConfigFile) -> Result<Self> {
+        let default = Self::default();
+        let features_count = config.get_or("extractor.features_count", default.features_count)?;
+
+        Ok(Self {
+            backend: match config.get::<String>("extractor.backend")? {
+                Some(backend) => match backend.as_str() {
+                    "orb" => ExtractorBackend::Orb,
+                    "akaze" => ExtractorBackend::Akaze,
+                    "brisk" => ExtractorBackend::Brisk,
+                    "sift" => ExtractorBackend::Sift,
+                    "gftt_brief" => ExtractorBackend::GfttBrief,
+                    #[cfg(feature = "native")]
+                    "native_orb" => ExtractorBackend::NativeOrb,
+                    _ => return Err(Error::from(ErrorKind::InvalidData)),
+                },
+                None => default.backend,
+            },
+            features_count,
+            scale_factor: config.get_or("extractor.scale_factor", default.scale_factor)?,
+            levels_count: config.get_or("extractor.levels_count", default.levels_count)?,
+            edge_threshold: config.get_or("extractor.edge_threshold", default.edge_threshold)?,
+            wta_k: config.get_or("extractor.wta_k", default.wta_k)?,
+            score_type: match config.get::<String>("extractor.score_type")? {
+                Some(score_type) => match score_type.as_str() {
+                    "harris" => ORB_ScoreType::HARRIS_SCORE,
+                    "fast" => ORB_ScoreType::FAST_SCORE,
+                    _ => return Err(Error::from(ErrorKind::InvalidData)),
+                },
+                None => default.score_type,
+            },
+            patch_size: config.get_or("extractor.patch_size", default.patch_size)?,
+            fast_threshold: config.get_or("extractor.fast_threshold", default.fast_threshold)?,
+            grid: match (
+                config.get::<i32>("extractor.grid_rows")?,
+                config.get::<i32>("extractor.grid_cols")?,
+            ) {
+                (Some(rows), Some(cols)) if rows > 0 && cols > 0 => Some(GridConfig {
+                    rows,
+                    cols,
+                    features_per_cell: config.get_or(
+                        "extractor.grid_features_per_cell",
+                        (features_count / (rows * cols)).max(1) as usize,
+                    )?,
+                    init_fast_threshold: config.get_or("extractor.grid_init_fast_threshold", 20)?,
+                    min_fast_threshold: config.get_or("extractor.grid_min_fast_threshold", 7)?,
+                }),
+                (None, None) => None,
+                _ => return Err(Error::from(ErrorKind::InvalidData)),
+            },
+            subpixel: if config.get_or("extractor.subpixel", false)? {
+                Some(SubpixelConfig {
+                    window_radius: config.get_or("extractor.subpixel_window_radius", 3)?,
+                    max_iterations: config.get_or("extractor.subpixel_max_iterations", 20)?,
+                    epsilon: config.get_or("extractor.subpixel_epsilon", 0.01)?,
+                })
+            } else {
+                None
+            },
+        })
+    }
+}
+
+impl Default for ExtractorConfig {
+    fn default() -> Self {
+        Self {
+            backend: ExtractorBackend::Orb,
+            features_count: 500,
+            scale_factor: 2.0,
+            levels_count: 8,
+            edge_threshold: 31,
+            wta_k: 2,
+            score_type: ORB_ScoreType::FAST_SCORE,
+            patch_size: 31,
+            fast_threshold: 100,
+            grid: None,
+            subpixel: None,
+        }
+    }
 }
 
 impl Extractor {
     pub fn new() -> Self {
+        Self::with_config(&ExtractorConfig::default())
+    }
+
+    pub fn with_config(config: &ExtractorConfig) -> Self {
+        let backend: Box<dyn FeatureExtractor> = match config.backend {
+            ExtractorBackend::Orb => Box::new(OrbExtractor::new(config)),
+            ExtractorBackend::Akaze => Box::new(AkazeExtractor::new(config)),
+            ExtractorBackend::Brisk => Box::new(BriskExtractor::new(config)),
+            ExtractorBackend::Sift => Box::new(SiftExtractor::new(config)),
+            ExtractorBackend::GfttBrief => Box::new(GfttBriefExtractor::new(config)),
+            #[cfg(feature = "native")]
+            ExtractorBackend::NativeOrb => Box::new(NativeOrbExtractor::new(config)),
+        };
+
+        let mut extractor = Self::with_backend(backend);
+        extractor.grid_detector = config
+            .grid
+            .map(|grid| GridDetector::new(grid, config.patch_size as f32).unwrap());
+        extractor.subpixel = config.subpixel;
+
+        extractor
+    }
+
+    pub fn with_backend(backend: Box<dyn FeatureExtractor>) -> Self {
         Self {
-            orb: ORB::create(500, 2.0, 8, 31, 0, 2, ORB_ScoreType::FAST_SCORE, 31, 100).unwrap(),
+            backend,
+            grid_detector: None,
+            subpixel: None,
+            mask: None,
         }
     }
 
-    pub async fn get_features(&mut self, src: &Mat) -> Features {
-        let mut keypoints = Vector::<KeyPoint>::new();
-        let mut descriptors = Mat::default().unwrap();
-        self.orb
-            .detect_and_compute(
-                src,
-                &no_array().unwrap(),
-                &mut keypoints,
-                &mut descriptors,
-                false,
-            )
-            .unwrap();
+    // 检测掩码，非零像素为有效区域
+    pub fn set_mask(&mut self, mask: Option<&Mat>) {
+        self.mask = mask.map(|mask| {
+            let mut dst = Mat::default().unwrap();
+            mask.copy_to(&mut dst).unwrap();
+            dst
+        });
+    }
+
+    // 网格检测与不接受外部特征点的后端组合时返回 InvalidInput
+    pub async fn get_features(&mut self, src: &Mat) -> Result<Features> {
+        let keypoints = match &mut self.grid_detector {
+            Some(grid_detector) => {
+                if !self.backend.accepts_external_keypoints() {
+                    return Err(Error::from(ErrorKind::InvalidInput));
+                }
+                grid_detector.detect(src, self.mask.as_ref())
+            }
+            None => self.backend.detect(src, self.mask.as_ref()),
+        }?;
 
-        Features {
+        // 固定特征点顺序，保证相同输入的输出一致
+        let mut sorted = keypoints.iter().collect::<Vec<KeyPoint>>();
+        sorted.sort_by(|a, b| {
+            b.response
+                .partial_cmp(&a.response)
+                .unwrap_or(Ordering::Equal)
+                .then(a.pt.y.partial_cmp(&b.pt.y).unwrap_or(Ordering::Equal))
+                .then(a.pt.x.partial_cmp(&b.pt.x).unwrap_or(Ordering::Equal))
+        });
+        let mut keypoints = Vector::<KeyPoint>::from_iter(sorted.into_iter());
+
+        let descriptors = self.backend.compute(src, &mut keypoints)?;
+
+        // 描述子按原位置计算，只精化输出位置
+        if let Some(subpixel) = &self.subpixel {
+            let backend = &self.backend;
+            refine_keypoints(src, &mut keypoints, subpixel, |octave| {
+                backend.get_octave_scale(octave)
+            });
+        }
+
+        Ok(Features {
             keypoints,
             descriptors,
+            descriptor_norm: self.backend.get_descriptor_norm(),
+        })
+    }
+}
+
+// 在特征点所在的金字塔层上精化，高层的量化误差为多个像素
+// 层的约定未知的特征点保持原位置
+fn refine_keypoints(
+    src: &Mat,
+    keypoints: &mut Vector<KeyPoint>,
+    config: &SubpixelConfig,
+    octave_scale: impl Fn(i32) -> Option<f64>,
+) {
+    let mut octaves = keypoints.iter().map(|kp| kp.octave).collect::<Vec<i32>>();
+    octaves.sort_unstable();
+    octaves.dedup();
+
+    for octave in octaves {
+        let scale = match octave_scale(octave).filter(|s| *s > 0.0) {
+            Some(scale) => scale,
+            None => continue,
+        };
+        let indices = keypoints
+            .iter()
+            .enumerate()
+            .filter(|(_, kp)| kp.octave == octave)
+            .map(|(i, _)| i)
+            .collect::<Vec<usize>>();
+
+        // 与 ORB 金字塔相同的缩放方式
+        let mut level = Mat::default().unwrap();
+        if scale != 1.0 {
+            let size = Size::new(
+                (src.cols() as f64 / scale).round() as i32,
+                (src.rows() as f64 / scale).round() as i32,
+            );
+            if size.width <= 0 || size.height <= 0 {
+                continue;
+            }
+            resize(src, &mut level, size, 0.0, 0.0, INTER_LINEAR).unwrap();
+        } else {
+            src.copy_to(&mut level).unwrap();
+        }
+
+        let to_level = |pt: Point2f| Point2f::new(pt.x / scale as f32, pt.y / scale as f32);
+        let mut corners = indices
+            .iter()
+            .map(|i| to_level(keypoints.get(*i).unwrap().pt))
+            .collect::<Vector<Point2f>>();
+        corner_sub_pix(
+            &level,
+            &mut corners,
+            Size::new(config.window_radius, config.window_radius),
+            Size::new(-1, -1),
+            TermCriteria::new(
+                TermCriteria_Type::COUNT as i32 + TermCriteria_Type::EPS as i32,
+                config.max_iterations,
+                config.epsilon,
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        // 在该层上移动超出窗口半径时认为没有收敛到角点，保留原位置
+        let max_distance = config.window_radius as f32;
+        for (i, pt) in indices.into_iter().zip(corners.iter()) {
+            let mut kp = keypoints.get(i).unwrap();
+            let origin = to_level(kp.pt);
+            let (dx, dy) = (pt.x - origin.x, pt.y - origin.y);
+            if dx * dx + dy * dy <= max_distance * max_distance {
+                kp.pt = Point2f::new(pt.x * scale as f32, pt.y * scale as f32);
+                keypoints.set(i, kp).unwrap();
+            }
         }
     }
 }
@@ -40,6 +295,109 @@ impl Extractor {
 mod test {
     use nalgebra::*;
 
+    use super::*;
+
+    #[test]
+    fn test_subpixel() {
+        let mut img =
+            Mat::new_rows_cols_with_default(100, 100, CV_8U, opencv::core::Scalar::all(0.0))
+                .unwrap();
+        rectangle(
+            &mut img,
+            Rect::new(50, 50, 50, 50),
+            opencv::core::Scalar::all(255.0),
+            FILLED,
+            LINE_8,
+            0,
+        )
+        .unwrap();
+        let mut blurred = Mat::default().unwrap();
+        gaussian_blur(
+            &img,
+            &mut blurred,
+            Size::new(3, 3),
+            0.0,
+            0.0,
+            BORDER_DEFAULT,
+        )
+        .unwrap();
+
+        let mut keypoints = opencv::core::Vector::<KeyPoint>::new();
+        keypoints
+            .push(KeyPoint::new_point(Point2f::new(51.0, 48.0), 7.0, -1.0, 0.0, 0, -1).unwrap());
+        // 金字塔第 1 层的特征点在缩小一半的图像上精化
+        keypoints
+            .push(KeyPoint::new_point(Point2f::new(53.0, 46.0), 14.0, -1.0, 0.0, 1, -1).unwrap());
+        // 层的约定未知时不精化
+        keypoints
+            .push(KeyPoint::new_point(Point2f::new(51.0, 48.0), 56.0, -1.0, 0.0, 3, -1).unwrap());
+        refine_keypoints(
+            &blurred,
+            &mut keypoints,
+            &SubpixelConfig {
+                window_radius: 5,
+                max_iterations: 40,
+                epsilon: 0.001,
+            },
+            |octave| {
+                if octave < 3 {
+                    Some(2.0f64.powi(octave))
+                } else {
+                    None
+                }
+            },
+        );
+
+        let pt = keypoints.get(0).unwrap().pt;
+        assert!((pt.x - 49.5).abs() < 0.5 && (pt.y - 49.5).abs() < 0.5);
+        // 缩放回原图时有半个像素的约定误差
+        let pt = keypoints.get(1).unwrap().pt;
+        assert!((pt.x - 49.5).abs() < 1.0 && (pt.y - 49.5).abs() < 1.0);
+        assert_eq!(keypoints.get(2).unwrap().pt, Point2f::new(51.0, 48.0));
+    }
+
+    #[async_std::test]
+    async fn test_grid_backend() {
+        let mut img =
+            Mat::new_rows_cols_with_default(120, 160, CV_8U, opencv::core::Scalar::all(0.0))
+                .unwrap();
+        rectangle(
+            &mut img,
+            Rect::new(40, 40, 60, 40),
+            opencv::core::Scalar::all(255.0),
+            FILLED,
+            LINE_8,
+            0,
+        )
+        .unwrap();
+
+        let grid = Some(GridConfig {
+            rows: 2,
+            cols: 2,
+            features_per_cell: 4,
+            init_fast_threshold: 20,
+            min_fast_threshold: 7,
+        });
+        for backend in [ExtractorBackend::Akaze, ExtractorBackend::Sift].iter() {
+            let mut extractor = Extractor::with_config(&ExtractorConfig {
+                backend: *backend,
+                grid,
+                ..ExtractorConfig::default()
+            });
+            assert_eq!(
+                extractor.get_features(&img).await.err().unwrap().kind(),
+                ErrorKind::InvalidInput
+            );
+        }
+
+        let mut extractor = Extractor::with_config(&ExtractorConfig {
+            grid,
+            ..ExtractorConfig::default()
+        });
+        let features = extractor.get_features(&img).await.unwrap();
+        assert_eq!(features.keypoints.len() as i32, features.descriptors.rows());
+    }
+
     #[test]
     fn test() {
         let p = Matrix3x4::new(
@@ -0,0 +1,227 @@
+use std::cmp::Ordering;
+
+use opencv::{core::*, features2d::*};
+
+use super::*;
+use crate::{Error, ErrorKind, Result};
+
+// FAST 检测需要的单元格外扩像素
+const CELL_PADDING: i32 = 3;
+
+#[derive(Copy, Clone)]
+pub struct GridConfig {
+    pub rows: i32,
+    pub cols: i32,
+    pub features_per_cell: usize,
+    pub init_fast_threshold: i32,
+    pub min_fast_threshold: i32,
+}
+
+pub struct GridDetector {
+    config: GridConfig,
+    keypoint_size: f32,
+    init_fast: Ptr<dyn FastFeatureDetector>,
+    min_fast: Ptr<dyn FastFeatureDetector>,
+}
+
+impl GridDetector {
+    // 行数与列数须为正数
+    pub fn new(config: GridConfig, keypoint_size: f32) -> Result<Self> {
+        if config.rows <= 0 || config.cols <= 0 {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+
+        let create_fast = |threshold| {
+            FastFeatureDetector::create(
+                threshold,
+                true,
+                FastFeatureDetector_DetectorType::TYPE_9_16,
+            )
+            .unwrap()
+        };
+
+        Ok(Self {
+            config,
+            keypoint_size,
+            init_fast: create_fast(config.init_fast_threshold),
+            min_fast: create_fast(config.min_fast_threshold),
+        })
+    }
+
+    pub fn detect(&mut self, src: &Mat, mask: Option<&Mat>) -> Result<Vector<KeyPoint>> {
+        let mut keypoints = Vector::<KeyPoint>::new();
+
+        let (width, height) = (src.cols(), src.rows());
+        let cell_width = width as f64 / self.config.cols as f64;
+        let cell_height = height as f64 / self.config.rows as f64;
+        for r in 0..self.config.rows {
+            for c in 0..self.config.cols {
+                let x_0 = (c as f64 * cell_width).round() as i32;
+                let x_1 = ((c + 1) as f64 * cell_width).round() as i32;
+                let y_0 = (r as f64 * cell_height).round() as i32;
+                let y_1 = ((r + 1) as f64 * cell_height).round() as i32;
+
+                let roi_x = (x_0 - CELL_PADDING).max(0);
+                let roi_y = (y_0 - CELL_PADDING).max(0);
+                let roi = Rect::new(
+                    roi_x,
+                    roi_y,
+                    (x_1 + CELL_PADDING).min(width) - roi_x,
+                    (y_1 + CELL_PADDING).min(height) - roi_y,
+                );
+                let cell = Mat::roi(src, roi).unwrap();
+                let cell_mask = mask.map(|mask| Mat::roi(mask, roi).unwrap());
+
+                // 纹理较弱的单元格降低阈值重新检测
+                let mut cell_keypoints =
+                    detect_with(&mut self.init_fast, &cell, cell_mask.as_ref())?;
+                if cell_keypoints.len() < self.config.features_per_cell {
+                    cell_keypoints = detect_with(&mut self.min_fast, &cell, cell_mask.as_ref())?;
+                }
+
+                let mut cell_keypoints = cell_keypoints
+                    .iter()
+                    .map(|mut kp| {
+                        kp.pt.x += roi_x as f32;
+                        kp.pt.y += roi_y as f32;
+                        kp.size = self.keypoint_size;
+                        kp.octave = 0;
+                        kp
+                    })
+                    .filter(|kp| {
+                        kp.pt.x >= x_0 as f32
+                            && kp.pt.x < x_1 as f32
+                            && kp.pt.y >= y_0 as f32
+                            && kp.pt.y < y_1 as f32
+                    })
+                    .collect::<Vec<KeyPoint>>();
+                cell_keypoints.sort_by(|a, b| {
+                    b.response
+                        .partial_cmp(&a.response)
+                        .unwrap_or(Ordering::Equal)
+                });
+                cell_keypoints.truncate(self.config.features_per_cell);
+
+                // 只在原图一层检测，方向按 ORB 的灰度质心计算以保留旋转不变性
+                for mut kp in cell_keypoints {
+                    kp.angle = ic_angle(src, kp.pt, (self.keypoint_size / 2.0) as i32)?;
+                    keypoints.push(kp);
+                }
+            }
+        }
+
+        Ok(keypoints)
+    }
+}
+
+// 灰度质心法求方向，同 ORB，角度制 [0, 360)
+fn ic_angle(src: &Mat, pt: Point2f, radius: i32) -> Result<f32> {
+    let (x, y) = (pt.x.round() as i32, pt.y.round() as i32);
+    let (mut m_01, mut m_10) = (0i64, 0i64);
+    for dy in -radius..=radius {
+        if y + dy < 0 || y + dy >= src.rows() {
+            continue;
+        }
+        let row = src
+            .at_row::<u8>(y + dy)
+            .map_err(|_| Error::from(ErrorKind::InvalidInput))?;
+        let half_width = ((radius * radius - dy * dy) as f64).sqrt() as i32;
+        for dx in -half_width..=half_width {
+            if x + dx >= 0 && x + dx < src.cols() {
+                let v = row[(x + dx) as usize] as i64;
+                m_10 += dx as i64 * v;
+                m_01 += dy as i64 * v;
+            }
+        }
+    }
+
+    let angle = (m_01 as f32).atan2(m_10 as f32).to_degrees();
+    Ok(if angle < 0.0 { angle + 360.0 } else { angle })
+}
+
+#[cfg(test)]
+mod test {
+    use opencv::imgproc::*;
+
+    use super::*;
+
+    #[test]
+    fn test() {
+        let mut img =
+            Mat::new_rows_cols_with_default(240, 320, CV_8U, opencv::core::Scalar::all(0.0))
+                .unwrap();
+        for i in 0..16 {
+            for j in 0..12 {
+                rectangle(
+                    &mut img,
+                    Rect::new(i * 20 + 5, j * 20 + 5, 10, 10),
+                    opencv::core::Scalar::all(255.0),
+                    FILLED,
+                    LINE_8,
+                    0,
+                )
+                .unwrap();
+            }
+        }
+
+        let config = GridConfig {
+            rows: 2,
+            cols: 2,
+            features_per_cell: 10,
+            init_fast_threshold: 20,
+            min_fast_threshold: 7,
+        };
+        let keypoints = GridDetector::new(config, 31.0)
+            .unwrap()
+            .detect(&img, None)
+            .unwrap();
+
+        let mut counts = [0; 4];
+        keypoints.iter().for_each(|kp| {
+            counts[(kp.pt.y >= 120.0) as usize * 2 + (kp.pt.x >= 160.0) as usize] += 1
+        });
+        assert!(counts.iter().all(|count| *count == 10));
+
+        assert!(GridDetector::new(GridConfig { rows: 0, ..config }, 31.0).is_err());
+        assert!(GridDetector::new(GridConfig { cols: -1, ..config }, 31.0).is_err());
+    }
+
+    #[test]
+    fn test_angle() {
+        let mut img =
+            Mat::new_rows_cols_with_default(100, 100, CV_8U, opencv::core::Scalar::all(0.0))
+                .unwrap();
+        rectangle(
+            &mut img,
+            Rect::new(40, 40, 40, 40),
+            opencv::core::Scalar::all(255.0),
+            FILLED,
+            LINE_8,
+            0,
+        )
+        .unwrap();
+
+        let config = GridConfig {
+            rows: 1,
+            cols: 1,
+            features_per_cell: 10,
+            init_fast_threshold: 20,
+            min_fast_threshold: 7,
+        };
+        let keypoints = GridDetector::new(config, 31.0)
+            .unwrap()
+            .detect(&img, None)
+            .unwrap();
+        assert_eq!(keypoints.len(), 4);
+        for kp in keypoints.iter() {
+            // 方向指向方块内部
+            let expected = match (kp.pt.x < 60.0, kp.pt.y < 60.0) {
+                (true, true) => 45.0,
+                (false, true) => 135.0,
+                (false, false) => 225.0,
+                (true, false) => 315.0,
+            };
+            assert!((kp.angle - expected).abs() < 10.0);
+        }
+    }
+}
@@ -0,0 +1,240 @@
+use nalgebra::*;
+use opencv::{core::*, imgproc::*, video::*};
+
+use super::*;
+
+pub struct KltConfig {
+    pub window_size: i32,
+    pub max_level: i32,
+    // 前向与反向跟踪结果的最大像素偏差
+    pub max_fb_error: f64,
+    // 跟踪点数低于该值时重新检测
+    pub min_tracked_count: usize,
+    pub max_features_count: i32,
+    pub quality_level: f64,
+    pub min_distance: f64,
+}
+
+// 以金字塔 LK 光流代替描述子匹配，输出与 Matcher 一致
+pub struct KltTracker {
+    config: KltConfig,
+    prev: Option<(Mat, opencv::core::Vector<Point2f>)>,
+    mask: Option<Mat>,
+}
+
+impl Default for KltConfig {
+    fn default() -> Self {
+        Self {
+            window_size: 21,
+            max_level: 3,
+            max_fb_error: 1.0,
+            min_tracked_count: 200,
+            max_features_count: 500,
+            quality_level: 0.01,
+            min_distance: 10.0,
+        }
+    }
+}
+
+impl KltTracker {
+    pub fn new() -> Self {
+        Self::with_config(KltConfig::default())
+    }
+
+    pub fn with_config(config: KltConfig) -> Self {
+        Self {
+            config,
+            prev: None,
+            mask: None,
+        }
+    }
+
+    // 检测掩码，非零像素为有效区域
+    pub fn set_mask(&mut self, mask: Option<&Mat>) {
+        self.mask = mask.map(|mask| {
+            let mut dst = Mat::default().unwrap();
+            mask.copy_to(&mut dst).unwrap();
+            dst
+        });
+    }
+
+    pub async fn process(&mut self, image: &Mat) -> Vec<MatchedFeature> {
+        let mut matched_features = Vec::new();
+        let mut points = opencv::core::Vector::<Point2f>::new();
+
+        if let Some((prev_image, prev_points)) = self.prev.take() {
+            if !prev_points.is_empty() {
+                let win_size = Size::new(self.config.window_size, self.config.window_size);
+                let criteria = TermCriteria::new(
+                    TermCriteria_Type::COUNT as i32 + TermCriteria_Type::EPS as i32,
+                    30,
+                    0.01,
+                )
+                .unwrap();
+                let track = |from: &Mat, to: &Mat, from_points: &opencv::core::Vector<Point2f>| {
+                    let mut to_points = opencv::core::Vector::<Point2f>::new();
+                    let mut status = opencv::core::Vector::<u8>::new();
+                    let mut err = opencv::core::Vector::<f32>::new();
+                    calc_optical_flow_pyr_lk(
+                        from,
+                        to,
+                        from_points,
+                        &mut to_points,
+                        &mut status,
+                        &mut err,
+                        win_size,
+                        self.config.max_level,
+                        criteria,
+                        0,
+                        1e-4,
+                    )
+                    .unwrap();
+
+                    (to_points, status)
+                };
+
+                let (next_points, next_status) = track(&prev_image, image, &prev_points);
+                let (back_points, back_status) = track(image, &prev_image, &next_points);
+
+                let (width, height) = (image.cols() as f32, image.rows() as f32);
+                for i in 0..prev_points.len() {
+                    let p = next_points.get(i).unwrap();
+                    if next_status.get(i).unwrap() == 0
+                        || back_status.get(i).unwrap() == 0
+                        || p.x < 0.0
+                        || p.y < 0.0
+                        || p.x >= width
+                        || p.y >= height
+                    {
+                        continue;
+                    }
+
+                    // 前后向一致性检验
+                    let prev_p = prev_points.get(i).unwrap();
+                    let back_p = back_points.get(i).unwrap();
+                    let fb_error =
+                        Vector2::new((back_p.x - prev_p.x) as f64, (back_p.y - prev_p.y) as f64)
+                            .norm();
+                    if fb_error < self.config.max_fb_error {
+                        matched_features.push(MatchedFeature {
+                            prev_index: i as u32,
+                            prev_frame_offset: 1,
+                            position: Vector2::new(p.x as f64, p.y as f64),
+                            match_degree: 1.0 - fb_error / self.config.max_fb_error,
+                            depth: None,
+                            descriptor: None,
+                        });
+                        points.push(p);
+                    }
+                }
+            }
+        }
+
+        if matched_features.len() < self.config.min_tracked_count {
+            let mut mask = match &self.mask {
+                Some(mask) => {
+                    let mut dst = Mat::default().unwrap();
+                    mask.copy_to(&mut dst).unwrap();
+                    dst
+                }
+                None => Mat::new_rows_cols_with_default(
+                    image.rows(),
+                    image.cols(),
+                    CV_8U,
+                    opencv::core::Scalar::all(255.0),
+                )
+                .unwrap(),
+            };
+            for p in points.iter() {
+                circle(
+                    &mut mask,
+                    opencv::core::Point::new(p.x as i32, p.y as i32),
+                    self.config.min_distance as i32,
+                    opencv::core::Scalar::all(0.0),
+                    FILLED,
+                    LINE_8,
+                    0,
+                )
+                .unwrap();
+            }
+
+            let max_corners = self.config.max_features_count - matched_features.len() as i32;
+            if max_corners > 0 {
+                let mut corners = opencv::core::Vector::<Point2f>::new();
+                good_features_to_track(
+                    image,
+                    &mut corners,
+                    max_corners,
+                    self.config.quality_level,
+                    self.config.min_distance,
+                    &mask,
+                    3,
+                    false,
+                    0.04,
+                )
+                .unwrap();
+
+                for p in corners {
+                    matched_features.push(MatchedFeature {
+                        prev_index: u32::MAX,
+                        prev_frame_offset: 1,
+                        position: Vector2::new(p.x as f64, p.y as f64),
+                        match_degree: 0.0,
+                        depth: None,
+                        descriptor: None,
+                    });
+                    points.push(p);
+                }
+            }
+        }
+
+        let mut prev_image = Mat::default().unwrap();
+        image.copy_to(&mut prev_image).unwrap();
+        self.prev = Some((prev_image, points));
+
+        matched_features
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[async_std::test]
+    async fn test() {
+        let draw = |offset_x, offset_y| {
+            let mut img =
+                Mat::new_rows_cols_with_default(240, 320, CV_8U, opencv::core::Scalar::all(0.0))
+                    .unwrap();
+            for i in 0..10 {
+                for j in 0..7 {
+                    rectangle(
+                        &mut img,
+                        Rect::new(i * 30 + 15 + offset_x, j * 30 + 15 + offset_y, 12, 12),
+                        opencv::core::Scalar::all(255.0),
+                        FILLED,
+                        LINE_8,
+                        0,
+                    )
+                    .unwrap();
+                }
+            }
+            img
+        };
+
+        let mut tracker = KltTracker::new();
+        let first = tracker.process(&draw(0, 0)).await;
+        assert!(first.iter().all(|mf| mf.prev_index == u32::MAX));
+
+        let second = tracker.process(&draw(2, 1)).await;
+        let tracked = second
+            .iter()
+            .filter(|mf| mf.prev_index != u32::MAX)
+            .collect::<Vec<_>>();
+        assert!(!tracked.is_empty());
+        for mf in tracked {
+            let prev = first[mf.prev_index as usize].position;
+            assert!((mf.position - prev - Vector2::new(2.0, 1.0)).norm() < 0.5);
+        }
+    }
+}
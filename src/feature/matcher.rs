@@ -1,86 +1,589 @@
+use std::collections::HashMap;
+
 use nalgebra::*;
-use opencv::{core::*, features2d::*};
+use opencv::{calib3d::*, core::*, features2d::*};
 
 use super::*;
+use crate::utils::ConfigFile;
+use crate::{Result, RnT};
+
+pub struct MatcherConfig {
+    // 最近邻与次近邻描述子距离之比的上限
+    pub ratio: f64,
+    // 为 None 时按描述子距离类型取 default_descriptor_distance
+    pub max_descriptor_distance: Option<f64>,
+    // 无运动预测时的像素搜索半径
+    pub search_radius: f64,
+    // 以预测位置为中心的像素搜索半径
+    pub guided_search_radius: f64,
+    // 不为 None 时用 RANSAC 拟合对极几何剔除外点
+    pub verification: Option<VerificationConfig>,
+    // 轨迹允许连续丢失的最大帧数，0 表示只与上一帧匹配
+    pub max_frame_gap: u32,
+}
+
+#[derive(Copy, Clone)]
+pub struct VerificationConfig {
+    pub threshold: f64,
+    pub confidence: f64,
+}
+
+#[derive(Copy, Clone, Default, Debug)]
+pub struct MatchStats {
+    pub features_count: usize,
+    pub matched_count: usize,
+    pub inliers_count: usize,
+    pub inlier_ratio: f64,
+}
 
 pub struct Matcher {
-    matcher: Ptr<BFMatcher>,
-    prev_computed: Option<(Mat, opencv::core::Vector<KeyPoint>)>,
+    config: MatcherConfig,
+    // 按描述子距离类型创建
+    matcher: Option<(i32, Ptr<BFMatcher>)>,
+    // 活动轨迹的最新描述子及观测
+    prev_computed: Option<(Mat, Vec<LiveFeature>)>,
+    predicted_homography: Option<Matrix3<f64>>,
+    camera_matrix: Option<Matrix3<f64>>,
+    stats: MatchStats,
+}
+
+struct LiveFeature {
+    // 所在帧与下一帧相隔的帧数
+    frame_offset: u32,
+    index: u32,
+    position: Vector2<f64>,
 }
 
 #[derive(Clone)]
 pub struct MatchedFeature {
     pub prev_index: u32,
+    // prev_index 所在帧与本帧相隔的帧数，1 表示上一帧
+    pub prev_frame_offset: u32,
     pub position: Vector2<f64>,
     pub match_degree: f64,
+    // 双目匹配得到的深度
+    pub depth: Option<f64>,
+    // 二进制描述子的原始字节，浮点描述子为 None
+    pub descriptor: Option<Vec<u8>>,
+}
+
+impl MatcherConfig {
+    pub fn from_config_file(config: &ConfigFile) -> Result<Self> {
+        let default = Self::default();
+
+        Ok(Self {
+            ratio: config.get_or("matcher.ratio", default.ratio)?,
+            max_descriptor_distance: config.get("matcher.max_descriptor_distance")?,
+            search_radius: config.get_or("matcher.search_radius", default.search_radius)?,
+            guided_search_radius: config
+                .get_or("matcher.guided_search_radius", default.guided_search_radius)?,
+            verification: if config.get_or("matcher.verification", false)? {
+                Some(VerificationConfig {
+                    threshold: config.get_or("matcher.verification_threshold", 1.0)?,
+                    confidence: config.get_or("matcher.verification_confidence", 0.99)?,
+                })
+            } else {
+                None
+            },
+            max_frame_gap: config.get_or("matcher.max_frame_gap", default.max_frame_gap)?,
+        })
+    }
+}
+
+impl Default for MatcherConfig {
+    fn default() -> Self {
+        Self {
+            ratio: 0.8,
+            max_descriptor_distance: None,
+            search_radius: 75.0,
+            guided_search_radius: 20.0,
+            verification: None,
+            max_frame_gap: 0,
+        }
+    }
+}
+
+// 描述子距离的默认上限，二进制描述子为汉明距离，SIFT 等浮点描述子为 L2 距离
+pub fn default_descriptor_distance(norm: i32) -> f64 {
+    match norm {
+        NORM_L1 | NORM_L2 => 250.0,
+        _ => 64.0,
+    }
 }
 
 impl Matcher {
     pub fn new() -> Self {
+        Self::with_config(MatcherConfig::default())
+    }
+
+    pub fn with_config(config: MatcherConfig) -> Self {
         Self {
-            matcher: BFMatcher::create(NORM_HAMMING, true).unwrap(),
+            config,
+            matcher: None,
             prev_computed: None,
+            predicted_homography: None,
+            camera_matrix: None,
+            stats: MatchStats::default(),
         }
     }
 
+    // 设置内参后几何校验拟合本质矩阵，否则拟合基础矩阵
+    pub fn set_camera_matrix(&mut self, camera_matrix: Option<Matrix3<f64>>) {
+        self.camera_matrix = camera_matrix;
+    }
+
+    // 最近一帧的匹配统计
+    pub fn get_stats(&self) -> &MatchStats {
+        &self.stats
+    }
+
+    // 设置上一帧到下一帧的预测运动（如恒速模型或 IMU 积分），仅作用于下一次匹配。
+    // 特征点无深度，只用旋转部分的单应 K·R·K⁻¹ 预测像素位置，
+    // 平移引起的视差不被预测，需由 guided_search_radius 覆盖。
+    pub fn set_prediction(&mut self, camera_matrix: &Matrix3<f64>, motion: &RnT) {
+        self.predicted_homography = camera_matrix.try_inverse().map(|inv_camera_matrix| {
+            camera_matrix
+                * UnitQuaternion::from_quaternion(motion.orientation_diff)
+                    .to_rotation_matrix()
+                    .into_inner()
+                * inv_camera_matrix
+        });
+    }
+
     pub async fn process(&mut self, features: Features) -> Vec<MatchedFeature> {
         let train_descriptors = features.descriptors;
         let train_keypoints = features.keypoints;
 
-        let matched_features = {
-            let get_vp = |x, y| Vector2::new(x as f64, y as f64);
+        let norm = features.descriptor_norm;
+        if self
+            .matcher
+            .as_ref()
+            .map(|(n, _)| *n != norm)
+            .unwrap_or(true)
+        {
+            self.matcher = Some((norm, BFMatcher::create(norm, false).unwrap()));
+            self.prev_computed = None;
+        }
+        let matcher = &mut self.matcher.as_mut().unwrap().1;
+        let max_descriptor_distance = self
+            .config
+            .max_descriptor_distance
+            .unwrap_or_else(|| default_descriptor_distance(norm));
 
-            let mut matched_features = train_keypoints
-                .iter()
-                .map(|kp| MatchedFeature {
-                    prev_index: u32::MAX,
-                    position: get_vp(kp.pt.x, kp.pt.y),
-                    match_degree: 0.0,
-                })
-                .collect::<Vec<MatchedFeature>>();
-
-            if let Some((query_descriptors, query_keypoints)) = self.prev_computed.take() {
-                let mut matches = opencv::core::Vector::<DMatch>::new();
-                if query_descriptors.cols() == train_descriptors.cols() {
-                    self.matcher
-                        .train_match(
-                            &query_descriptors,
-                            &train_descriptors,
-                            &mut matches,
-                            &no_array().unwrap(),
-                        )
-                        .unwrap_or_default();
+        let get_vp = |x, y| Vector2::new(x as f64, y as f64);
+
+        let binary = train_descriptors.typ().unwrap() == CV_8U;
+        let mut matched_features = train_keypoints
+            .iter()
+            .enumerate()
+            .map(|(j, kp)| MatchedFeature {
+                prev_index: u32::MAX,
+                prev_frame_offset: 1,
+                position: get_vp(kp.pt.x, kp.pt.y),
+                match_degree: 0.0,
+                depth: None,
+                descriptor: if binary {
+                    train_descriptors
+                        .at_row::<u8>(j as i32)
+                        .ok()
+                        .map(|row| row.to_vec())
+                } else {
+                    None
+                },
+            })
+            .collect::<Vec<MatchedFeature>>();
+
+        let predicted_homography = self.predicted_homography.take();
+        let prev_computed = self.prev_computed.take();
+        // 被本帧特征点延续的活动轨迹
+        let mut continued = vec![false; prev_computed.as_ref().map(|(_, l)| l.len()).unwrap_or(0)];
+        if let Some((query_descriptors, live_features)) = &prev_computed {
+            // 只在搜索窗口内匹配
+            let mut mask = Mat::new_rows_cols_with_default(
+                live_features.len() as i32,
+                train_keypoints.len() as i32,
+                CV_8U,
+                opencv::core::Scalar::all(0.0),
+            )
+            .unwrap();
+            // 按搜索半径划分网格，每个活动轨迹只检查中心附近的单元格
+            let cell_size = self
+                .config
+                .search_radius
+                .max(self.config.guided_search_radius)
+                .max(1.0);
+            let get_cell = |p: &Vector2<f64>| {
+                (
+                    (p.x / cell_size).floor() as i64,
+                    (p.y / cell_size).floor() as i64,
+                )
+            };
+            let mut cells = HashMap::<(i64, i64), Vec<usize>>::new();
+            for (j, mf) in matched_features.iter().enumerate() {
+                cells.entry(get_cell(&mf.position)).or_default().push(j);
+            }
+
+            for (i, live) in live_features.iter().enumerate() {
+                // 运动预测只适用于上一帧的观测
+                let predicted = predicted_homography
+                    .filter(|_| live.frame_offset == 1)
+                    .and_then(|h| {
+                        let p = h * Vector3::new(live.position.x, live.position.y, 1.0);
+                        if p.z > 0.0 {
+                            Some(Vector2::new(p.x / p.z, p.y / p.z))
+                        } else {
+                            None
+                        }
+                    });
+                let (center, radius) = match predicted {
+                    Some(p) => (p, self.config.guided_search_radius),
+                    None => (live.position, self.config.search_radius),
+                };
+
+                let row = mask.at_row_mut::<u8>(i as i32).unwrap();
+                let (cell_x, cell_y) = get_cell(&center);
+                let cells_radius = (radius / cell_size).ceil() as i64;
+                for x in cell_x - cells_radius..=cell_x + cells_radius {
+                    for y in cell_y - cells_radius..=cell_y + cells_radius {
+                        for j in cells.get(&(x, y)).into_iter().flatten() {
+                            let v = matched_features[*j].position - center;
+                            if v.dot(&v) <= radius.powi(2) {
+                                row[*j] = 1;
+                            }
+                        }
+                    }
+                }
+            }
+
+            let mut knn_matches = opencv::core::Vector::<opencv::core::Vector<DMatch>>::new();
+            if query_descriptors.cols() == train_descriptors.cols() {
+                matcher
+                    .knn_train_match(
+                        query_descriptors,
+                        &train_descriptors,
+                        &mut knn_matches,
+                        2,
+                        &mask,
+                        false,
+                    )
+                    .unwrap_or_default();
+            }
+
+            let mut best_matches: Vec<Option<(usize, f64)>> = vec![None; matched_features.len()];
+            for knn in knn_matches {
+                let m = match knn.get(0) {
+                    Ok(m) => m,
+                    Err(_) => continue,
+                };
+
+                let distance = m.distance as f64;
+                if distance >= max_descriptor_distance {
+                    continue;
                 }
 
-                for m in matches {
-                    let query_kp = &query_keypoints.get(m.query_idx as usize).unwrap();
-                    let train_kp = &train_keypoints.get(m.train_idx as usize).unwrap();
-
-                    // 考虑匹配的两点距离
-                    let query_kp_vp = get_vp(query_kp.pt.x, query_kp.pt.y);
-                    let train_kp_vp = get_vp(train_kp.pt.x, train_kp.pt.y);
-                    const DISTANCE_THRESHOLD: f64 = 75.0;
-                    let v = query_kp_vp - train_kp_vp;
-                    if v.dot(&v) <= DISTANCE_THRESHOLD.powi(2) {
-                        let mf = &mut matched_features[m.train_idx as usize];
-                        mf.prev_index = m.query_idx as u32;
-                        // TODO:
-                        mf.match_degree = 1.0;
+                // Lowe 比率检验，搜索范围内只有一个候选时没有歧义，只按描述子距离计算
+                let ratio = match knn.get(1) {
+                    Ok(second) => {
+                        let ratio = if second.distance > 0.0 {
+                            distance / second.distance as f64
+                        } else {
+                            1.0
+                        };
+                        if ratio >= self.config.ratio {
+                            continue;
+                        }
+                        ratio
                     }
+                    Err(_) => distance / max_descriptor_distance,
+                };
+
+                let train_idx = m.train_idx as usize;
+                if best_matches[train_idx]
+                    .map(|(_, d)| distance < d)
+                    .unwrap_or(true)
+                {
+                    best_matches[train_idx] = Some((m.query_idx as usize, distance));
+
+                    // 描述子距离越小、与次近邻区分度越高，匹配置信度越高
+                    let live = &live_features[m.query_idx as usize];
+                    let mf = &mut matched_features[train_idx];
+                    mf.prev_index = live.index;
+                    mf.prev_frame_offset = live.frame_offset;
+                    mf.match_degree = (1.0 - distance / max_descriptor_distance) * (1.0 - ratio);
                 }
             }
 
-            matched_features
+            if let Some(verification) = &self.config.verification {
+                verify_epipolar(
+                    verification,
+                    self.camera_matrix.as_ref(),
+                    live_features,
+                    &best_matches,
+                    &mut matched_features,
+                );
+            }
+
+            for (mf, best) in matched_features.iter().zip(best_matches.iter()) {
+                if let Some((query_idx, _)) = best {
+                    if mf.match_degree > 0.0 {
+                        continued[*query_idx] = true;
+                    }
+                }
+            }
+        }
+
+        let matched_count = matched_features
+            .iter()
+            .filter(|mf| mf.prev_index != u32::MAX)
+            .count();
+        let inliers_count = matched_features
+            .iter()
+            .filter(|mf| mf.prev_index != u32::MAX && mf.match_degree > 0.0)
+            .count();
+        self.stats = MatchStats {
+            features_count: matched_features.len(),
+            matched_count,
+            inliers_count,
+            inlier_ratio: if matched_count > 0 {
+                inliers_count as f64 / matched_count as f64
+            } else {
+                0.0
+            },
         };
 
-        self.prev_computed = Some((train_descriptors, train_keypoints));
+        // 本帧特征点加上未被延续且未超过最大间隔的旧轨迹
+        let mut live_descriptors = train_descriptors;
+        let mut live_features = matched_features
+            .iter()
+            .enumerate()
+            .map(|(j, mf)| LiveFeature {
+                frame_offset: 1,
+                index: j as u32,
+                position: mf.position,
+            })
+            .collect::<Vec<LiveFeature>>();
+        if let Some((query_descriptors, prev_live_features)) = prev_computed {
+            for (i, live) in prev_live_features.into_iter().enumerate() {
+                if !continued[i] && live.frame_offset <= self.config.max_frame_gap {
+                    live_descriptors
+                        .push_back(&query_descriptors.row(i as i32).unwrap())
+                        .unwrap();
+                    live_features.push(LiveFeature {
+                        frame_offset: live.frame_offset + 1,
+                        ..live
+                    });
+                }
+            }
+        }
+        self.prev_computed = Some((live_descriptors, live_features));
 
         matched_features
     }
 }
 
+// 只校验与上一帧的匹配，外点保留 prev_index，match_degree 置 0
+fn verify_epipolar(
+    config: &VerificationConfig,
+    camera_matrix: Option<&Matrix3<f64>>,
+    live_features: &[LiveFeature],
+    best_matches: &[Option<(usize, f64)>],
+    matched_features: &mut [MatchedFeature],
+) {
+    let pairs = best_matches
+        .iter()
+        .enumerate()
+        .filter_map(|(j, best)| best.map(|(i, _)| (i, j)))
+        .filter(|(i, _)| live_features[*i].frame_offset == 1)
+        .collect::<Vec<(usize, usize)>>();
+
+    // 匹配点太少或拟合失败时不做标记
+    let min_points_count = if camera_matrix.is_some() { 5 } else { 8 };
+    if pairs.len() < min_points_count {
+        return;
+    }
+
+    let mut points_0 = opencv::core::Vector::<Point2d>::new();
+    let mut points_1 = opencv::core::Vector::<Point2d>::new();
+    for (i, j) in pairs.iter() {
+        let p_0 = live_features[*i].position;
+        let p_1 = matched_features[*j].position;
+        points_0.push(Point2d::new(p_0.x, p_0.y));
+        points_1.push(Point2d::new(p_1.x, p_1.y));
+    }
+
+    let mut mask = Mat::default().unwrap();
+    let fitted = match camera_matrix {
+        Some(camera_matrix) => find_essential_mat_matrix(
+            &points_0,
+            &points_1,
+            &crate::utils::matrix_to_mat(camera_matrix),
+            RANSAC,
+            config.confidence,
+            config.threshold,
+            &mut mask,
+        ),
+        None => find_fundamental_mat(
+            &points_0,
+            &points_1,
+            FM_RANSAC,
+            config.threshold,
+            config.confidence,
+            1000,
+            &mut mask,
+        ),
+    }
+    .map(|m| !m.empty().unwrap_or(true))
+    .unwrap_or(false);
+
+    if fitted {
+        for (k, (_, j)) in pairs.into_iter().enumerate() {
+            if *mask.at::<u8>(k as i32).unwrap() == 0 {
+                matched_features[j].match_degree = 0.0;
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
+    use super::*;
+
     #[test]
-    fn test() {}
+    fn test() {
+        let config =
+            MatcherConfig::from_config_file(&ConfigFile::parse("matcher.ratio = 0.7\n").unwrap())
+                .unwrap();
+        assert_eq!(config.ratio, 0.7);
+        assert_eq!(config.max_descriptor_distance, None);
+        assert_eq!(default_descriptor_distance(NORM_HAMMING), 64.0);
+        assert_eq!(default_descriptor_distance(NORM_L2), 250.0);
+    }
+
+    fn features(points: &[(f32, f32)], descriptors: &[Vec<f32>], typ: i32, norm: i32) -> Features {
+        let mut mat = Mat::new_rows_cols_with_default(
+            descriptors.len() as i32,
+            descriptors[0].len() as i32,
+            typ,
+            opencv::core::Scalar::all(0.0),
+        )
+        .unwrap();
+        for (i, row) in descriptors.iter().enumerate() {
+            for (j, v) in row.iter().enumerate() {
+                if typ == CV_8U {
+                    *mat.at_2d_mut::<u8>(i as i32, j as i32).unwrap() = *v as u8;
+                } else {
+                    *mat.at_2d_mut::<f32>(i as i32, j as i32).unwrap() = *v;
+                }
+            }
+        }
+
+        Features {
+            keypoints: points
+                .iter()
+                .map(|(x, y)| KeyPoint::new_coords(*x, *y, 31.0, 0.0, 1.0, 0, -1).unwrap())
+                .collect(),
+            descriptors: mat,
+            descriptor_norm: norm,
+        }
+    }
+
+    #[async_std::test]
+    async fn test_descriptor_distance() {
+        // SIFT 量级的 L2 距离在默认上限内可以匹配
+        let a = (0..128).map(|i| if i < 64 { 40.0 } else { 0.0 }).collect();
+        let b = (0..128).map(|i| if i < 64 { 0.0 } else { 40.0 }).collect();
+        let a_noisy = (0..128)
+            .map(|i| if i < 64 { 40.0 } else { 0.0 } + if i % 2 == 0 { 10.0 } else { 0.0 })
+            .collect::<Vec<f32>>();
+        let points = [(10.0, 10.0), (20.0, 10.0)];
+        let mut matcher = Matcher::new();
+        matcher
+            .process(features(&points, &[a, b.clone()], CV_32F, NORM_L2))
+            .await;
+        let matched = matcher
+            .process(features(&points, &[a_noisy, b], CV_32F, NORM_L2))
+            .await;
+        assert_eq!(matched[0].prev_index, 0);
+        assert!(matched[0].match_degree > 0.0);
+
+        // 只有一个候选时直接接受，置信度只取决于描述子距离
+        let mut matcher = Matcher::new();
+        let descriptor = vec![vec![0xa5 as f32; 32]];
+        matcher
+            .process(features(&points[..1], &descriptor, CV_8U, NORM_HAMMING))
+            .await;
+        let matched = matcher
+            .process(features(&points[..1], &descriptor, CV_8U, NORM_HAMMING))
+            .await;
+        assert_eq!(matched[0].prev_index, 0);
+        assert_eq!(matched[0].match_degree, 1.0);
+    }
+
+    #[async_std::test]
+    async fn test_verify_epipolar() {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        use crate::utils::test_scene::*;
+
+        let camera_matrix = camera_matrix();
+        let positions = grid_landmarks(60, 10, 0.8, |i| 5.0 + (i % 7) as f64);
+        let mut rng = StdRng::seed_from_u64(0);
+        let descriptors = (0..positions.len())
+            .map(|_| (0..32).map(|_| rng.gen::<u8>() as f32).collect())
+            .collect::<Vec<Vec<f32>>>();
+
+        // 相机沿 x 轴平移 0.3，前 6 个点在第 1 帧沿 y 方向偏离对极线 25 像素
+        let project_frame = |frame: usize| {
+            let t = Vector3::new(-0.3 * frame as f64, 0.0, 0.0);
+            project_points(&camera_matrix, &positions, &Matrix3::identity(), &t)
+                .iter()
+                .enumerate()
+                .map(|(i, p)| {
+                    let offset = if frame == 1 && i < 6 { 25.0 } else { 0.0 };
+                    (p.x as f32, (p.y + offset) as f32)
+                })
+                .collect::<Vec<(f32, f32)>>()
+        };
+
+        let mut matcher = Matcher::with_config(MatcherConfig {
+            verification: Some(VerificationConfig {
+                threshold: 1.0,
+                confidence: 0.99,
+            }),
+            ..MatcherConfig::default()
+        });
+        matcher.set_camera_matrix(Some(camera_matrix));
+        matcher
+            .process(features(
+                &project_frame(0),
+                &descriptors,
+                CV_8U,
+                NORM_HAMMING,
+            ))
+            .await;
+        let matched = matcher
+            .process(features(
+                &project_frame(1),
+                &descriptors,
+                CV_8U,
+                NORM_HAMMING,
+            ))
+            .await;
+
+        for (j, mf) in matched.iter().enumerate() {
+            // 外点保留 prev_index，只将 match_degree 置 0
+            assert_eq!(mf.prev_index, j as u32);
+            if j < 6 {
+                assert_eq!(mf.match_degree, 0.0);
+            } else {
+                assert!(mf.match_degree > 0.0);
+            }
+        }
+
+        let stats = matcher.get_stats();
+        assert_eq!(stats.features_count, 60);
+        assert_eq!(stats.matched_count, 60);
+        assert_eq!(stats.inliers_count, 54);
+        assert!((stats.inlier_ratio - 0.9).abs() < 1e-9);
+    }
 }
@@ -1,5 +1,29 @@
+#[cfg(feature = "opencv")]
+mod backend;
+#[cfg(feature = "opencv")]
 mod extractor;
+#[cfg(feature = "opencv")]
+mod grid;
+#[cfg(feature = "opencv")]
+mod klt;
+#[cfg(feature = "opencv")]
 mod matcher;
+#[cfg(feature = "native")]
+mod native;
+#[cfg(feature = "opencv")]
+mod stereo;
 
+#[cfg(feature = "opencv")]
+pub use backend::*;
+#[cfg(feature = "opencv")]
 pub use extractor::*;
+#[cfg(feature = "opencv")]
+pub use grid::*;
+#[cfg(feature = "opencv")]
+pub use klt::*;
+#[cfg(feature = "opencv")]
 pub use matcher::*;
+#[cfg(feature = "native")]
+pub use native::*;
+#[cfg(feature = "opencv")]
+pub use stereo::*;
@@ -0,0 +1,198 @@
+use opencv::core::*;
+
+use crate::feature::*;
+use crate::{Error, ErrorKind, Result};
+
+pub struct NativeOrbExtractor {
+    orb: NativeOrb,
+    scale_factor: f32,
+}
+
+impl GrayImage {
+    // 仅支持 CV_8UC1
+    pub fn from_mat(mat: &Mat) -> Result<Self> {
+        if mat.typ().unwrap() != CV_8UC1 {
+            return Err(Error::from(ErrorKind::InvalidInput));
+        }
+
+        let (width, height) = (mat.cols() as usize, mat.rows() as usize);
+        let mut data = Vec::with_capacity(width * height);
+        for y in 0..height {
+            data.extend_from_slice(mat.at_row::<u8>(y as i32).unwrap());
+        }
+
+        Self::new(width, height, data)
+    }
+
+    pub fn to_mat(&self) -> Mat {
+        let mut mat = Mat::new_rows_cols_with_default(
+            self.get_height() as i32,
+            self.get_width() as i32,
+            CV_8UC1,
+            opencv::core::Scalar::all(0.0),
+        )
+        .unwrap();
+        for (y, row) in self.get_data().chunks(self.get_width()).enumerate() {
+            mat.at_row_mut::<u8>(y as i32).unwrap().copy_from_slice(row);
+        }
+
+        mat
+    }
+}
+
+impl From<&NativeKeyPoint> for KeyPoint {
+    fn from(kp: &NativeKeyPoint) -> Self {
+        KeyPoint::new_coords(kp.x, kp.y, kp.size, kp.angle, kp.response, kp.octave, -1).unwrap()
+    }
+}
+
+impl From<&KeyPoint> for NativeKeyPoint {
+    fn from(kp: &KeyPoint) -> Self {
+        Self {
+            x: kp.pt.x,
+            y: kp.pt.y,
+            size: kp.size,
+            angle: kp.angle,
+            response: kp.response,
+            octave: kp.octave,
+        }
+    }
+}
+
+pub fn descriptors_to_mat(descriptors: &[Descriptor]) -> Mat {
+    let mut mat = Mat::new_rows_cols_with_default(
+        descriptors.len() as i32,
+        32,
+        CV_8UC1,
+        opencv::core::Scalar::all(0.0),
+    )
+    .unwrap();
+    for (i, descriptor) in descriptors.iter().enumerate() {
+        mat.at_row_mut::<u8>(i as i32)
+            .unwrap()
+            .copy_from_slice(descriptor);
+    }
+
+    mat
+}
+
+// 仅支持 32 字节的 CV_8UC1 描述子
+pub fn mat_to_descriptors(mat: &Mat) -> Result<Vec<Descriptor>> {
+    if mat.rows() > 0 && (mat.typ().unwrap() != CV_8UC1 || mat.cols() != 32) {
+        return Err(Error::from(ErrorKind::InvalidInput));
+    }
+
+    Ok((0..mat.rows())
+        .map(|i| {
+            let mut descriptor = [0u8; 32];
+            descriptor.copy_from_slice(mat.at_row::<u8>(i).unwrap());
+            descriptor
+        })
+        .collect())
+}
+
+impl From<NativeFeatures> for Features {
+    fn from(features: NativeFeatures) -> Self {
+        Self {
+            keypoints: features.keypoints.iter().map(KeyPoint::from).collect(),
+            descriptors: descriptors_to_mat(&features.descriptors),
+            descriptor_norm: NORM_HAMMING,
+        }
+    }
+}
+
+impl NativeOrbExtractor {
+    pub fn new(config: &ExtractorConfig) -> Self {
+        Self {
+            orb: NativeOrb::new(NativeOrbConfig {
+                features_count: config.features_count.max(0) as usize,
+                scale_factor: config.scale_factor,
+                levels_count: config.levels_count.max(1) as usize,
+                edge_threshold: config.edge_threshold.max(0) as usize,
+                fast_threshold: config.fast_threshold.max(0).min(255) as u8,
+            }),
+            scale_factor: config.scale_factor,
+        }
+    }
+}
+
+impl FeatureExtractor for NativeOrbExtractor {
+    fn get_descriptor_norm(&self) -> i32 {
+        NORM_HAMMING
+    }
+
+    fn detect(&mut self, src: &Mat, mask: Option<&Mat>) -> Result<opencv::core::Vector<KeyPoint>> {
+        let image = GrayImage::from_mat(src)?;
+        let mask = match mask {
+            Some(mask) => Some(GrayImage::from_mat(mask)?),
+            None => None,
+        };
+
+        Ok(self
+            .orb
+            .detect(&image, mask.as_ref())
+            .iter()
+            .map(KeyPoint::from)
+            .collect())
+    }
+
+    fn compute(
+        &mut self,
+        src: &Mat,
+        keypoints: &mut opencv::core::Vector<KeyPoint>,
+    ) -> Result<Mat> {
+        let image = GrayImage::from_mat(src)?;
+        let mut native = keypoints
+            .iter()
+            .map(|kp| NativeKeyPoint::from(&kp))
+            .collect::<Vec<NativeKeyPoint>>();
+        let descriptors = self.orb.compute(&image, &mut native);
+        *keypoints = native.iter().map(KeyPoint::from).collect();
+
+        Ok(descriptors_to_mat(&descriptors))
+    }
+
+    fn get_octave_scale(&self, octave: i32) -> Option<f64> {
+        Some((self.scale_factor as f64).powi(octave))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let image = GrayImage::new(
+            64,
+            48,
+            (0..64 * 48).map(|i| ((i * 7919) % 251) as u8).collect(),
+        )
+        .unwrap();
+        let mat = image.to_mat();
+        assert_eq!(
+            GrayImage::from_mat(&mat).unwrap().get_data(),
+            image.get_data()
+        );
+
+        let features = Features::from(NativeFeatures {
+            keypoints: vec![NativeKeyPoint {
+                x: 10.0,
+                y: 20.0,
+                size: 31.0,
+                angle: 90.0,
+                response: 1.0,
+                octave: 0,
+            }],
+            descriptors: vec![[0xa5; 32]],
+        });
+        assert_eq!(features.keypoints.len(), 1);
+        assert_eq!(features.descriptors.rows(), 1);
+        assert_eq!(features.descriptor_norm, NORM_HAMMING);
+        assert_eq!(*features.descriptors.at_2d::<u8>(0, 31).unwrap(), 0xa5);
+        assert_eq!(
+            mat_to_descriptors(&features.descriptors).unwrap(),
+            vec![[0xa5; 32]]
+        );
+    }
+}
@@ -0,0 +1,169 @@
+use super::*;
+
+// 半径为 3 的 Bresenham 圆
+const CIRCLE: [(i32, i32); 16] = [
+    (0, -3),
+    (1, -3),
+    (2, -2),
+    (3, -1),
+    (3, 0),
+    (3, 1),
+    (2, 2),
+    (1, 3),
+    (0, 3),
+    (-1, 3),
+    (-2, 2),
+    (-3, 1),
+    (-3, 0),
+    (-3, -1),
+    (-2, -2),
+    (-1, -3),
+];
+
+const ARC_LENGTH: usize = 9;
+
+// FAST-9 角点检测，返回经过 3x3 非极大值抑制的 (x, y, 得分)
+pub fn fast_detect(image: &GrayImage, threshold: u8, border: usize) -> Vec<(usize, usize, f32)> {
+    let (width, height) = (image.get_width(), image.get_height());
+    let border = border.max(3);
+    if width <= 2 * border || height <= 2 * border {
+        return Vec::new();
+    }
+
+    let mut scores = vec![0.0f32; width * height];
+    for y in border..height - border {
+        for x in border..width - border {
+            if let Some(score) = corner_score(image, x, y, threshold) {
+                scores[y * width + x] = score;
+            }
+        }
+    }
+
+    let mut corners = Vec::new();
+    for y in border..height - border {
+        for x in border..width - border {
+            let score = scores[y * width + x];
+            if score > 0.0 {
+                let is_max = (-1..=1).all(|dy: i32| {
+                    (-1..=1).all(|dx: i32| {
+                        let (nx, ny) = ((x as i32 + dx) as usize, (y as i32 + dy) as usize);
+                        let neighbor = scores[ny * width + nx];
+                        // 得分相同时保留扫描顺序靠前的点
+                        (dx == 0 && dy == 0)
+                            || neighbor < score
+                            || (neighbor == score && (dy, dx) > (0, 0))
+                    })
+                });
+                if is_max {
+                    corners.push((x, y, score));
+                }
+            }
+        }
+    }
+
+    corners
+}
+
+fn corner_score(image: &GrayImage, x: usize, y: usize, threshold: u8) -> Option<f32> {
+    let center = image.get(x, y) as i32;
+    let threshold = threshold as i32;
+    let pixel = |i: usize| {
+        let (dx, dy) = CIRCLE[i];
+        image.get((x as i32 + dx) as usize, (y as i32 + dy) as usize) as i32
+    };
+
+    // 长度为 9 的连续弧至少覆盖 4 个方向点中的 2 个
+    let (mut brighter, mut darker) = (0, 0);
+    for i in (0..16).step_by(4) {
+        let v = pixel(i);
+        if v > center + threshold {
+            brighter += 1;
+        } else if v < center - threshold {
+            darker += 1;
+        }
+    }
+    if brighter < 2 && darker < 2 {
+        return None;
+    }
+
+    let values = (0..16).map(pixel).collect::<Vec<i32>>();
+    let has_arc = |check: &dyn Fn(i32) -> bool| {
+        let mut run = 0;
+        for i in 0..16 + ARC_LENGTH {
+            if check(values[i % 16]) {
+                run += 1;
+                if run >= ARC_LENGTH {
+                    return true;
+                }
+            } else {
+                run = 0;
+            }
+        }
+        false
+    };
+
+    let is_brighter = has_arc(&|v| v > center + threshold);
+    let is_darker = has_arc(&|v| v < center - threshold);
+    if !is_brighter && !is_darker {
+        return None;
+    }
+
+    // 得分为超过阈值部分的绝对差之和
+    let score = values
+        .iter()
+        .map(|v| {
+            let diff = if is_brighter { v - center } else { center - v };
+            (diff - threshold).max(0)
+        })
+        .sum::<i32>();
+
+    Some(score as f32)
+}
+
+// 以 Sobel 梯度计算 Harris 响应
+pub fn harris_response(image: &GrayImage, x: usize, y: usize, block_radius: i32) -> f32 {
+    const K: f32 = 0.04;
+
+    let get = |x: i32, y: i32| image.get_clamped(x, y) as f32;
+    let (mut a, mut b, mut c) = (0.0, 0.0, 0.0);
+    for dy in -block_radius..=block_radius {
+        for dx in -block_radius..=block_radius {
+            let (px, py) = (x as i32 + dx, y as i32 + dy);
+            let ix = (get(px + 1, py - 1) + 2.0 * get(px + 1, py) + get(px + 1, py + 1))
+                - (get(px - 1, py - 1) + 2.0 * get(px - 1, py) + get(px - 1, py + 1));
+            let iy = (get(px - 1, py + 1) + 2.0 * get(px, py + 1) + get(px + 1, py + 1))
+                - (get(px - 1, py - 1) + 2.0 * get(px, py - 1) + get(px + 1, py - 1));
+            a += ix * ix;
+            b += iy * iy;
+            c += ix * iy;
+        }
+    }
+
+    a * b - c * c - K * (a + b) * (a + b)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let mut data = vec![0u8; 64 * 64];
+        for y in 20..40 {
+            for x in 20..40 {
+                data[y * 64 + x] = 255;
+            }
+        }
+        let image = GrayImage::new(64, 64, data).unwrap();
+
+        let corners = fast_detect(&image, 20, 3);
+        assert_eq!(corners.len(), 4);
+        for (x, y, _) in corners.iter() {
+            assert!((*x == 20 || *x == 39) && (*y == 20 || *y == 39));
+        }
+
+        // 角点的 Harris 响应为正，边缘为负
+        assert!(harris_response(&image, 20, 20, 3) > 0.0);
+        assert!(harris_response(&image, 30, 20, 3) < 0.0);
+    }
+}
@@ -0,0 +1,354 @@
+use std::collections::HashMap;
+
+use super::*;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct NativeMatch {
+    pub query_index: usize,
+    pub train_index: usize,
+    pub distance: u32,
+}
+
+// 256 位描述子按 u64 存储，便于 popcount
+type Packed = [u64; 4];
+
+fn pack(descriptor: &Descriptor) -> Packed {
+    let mut packed = [0u64; 4];
+    for (i, chunk) in descriptor.chunks(8).enumerate() {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(chunk);
+        packed[i] = u64::from_le_bytes(bytes);
+    }
+
+    packed
+}
+
+// 内联到扫描循环中，所在函数启用 popcnt 时 count_ones 编译为单条指令
+#[inline(always)]
+fn distance(a: &Packed, b: &Packed) -> u32 {
+    (a[0] ^ b[0]).count_ones()
+        + (a[1] ^ b[1]).count_ones()
+        + (a[2] ^ b[2]).count_ones()
+        + (a[3] ^ b[3]).count_ones()
+}
+
+#[cfg(target_arch = "x86_64")]
+fn has_popcnt() -> bool {
+    is_x86_feature_detected!("popcnt")
+}
+
+pub fn hamming_distance(a: &Descriptor, b: &Descriptor) -> u32 {
+    distance(&pack(a), &pack(b))
+}
+
+// 按距离保留最近的 k 个候选，k 很小时插入排序最快
+fn push_candidate(best: &mut Vec<NativeMatch>, k: usize, candidate: NativeMatch) {
+    if best.len() == k && best[k - 1].distance <= candidate.distance {
+        return;
+    }
+
+    let pos = best
+        .iter()
+        .position(|m| m.distance > candidate.distance)
+        .unwrap_or(best.len());
+    best.insert(pos, candidate);
+    best.truncate(k);
+}
+
+pub struct HammingMatcher {
+    train: Vec<Packed>,
+}
+
+impl HammingMatcher {
+    pub fn new(train: &[Descriptor]) -> Self {
+        Self {
+            train: train.iter().map(pack).collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.train.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.train.is_empty()
+    }
+
+    pub fn knn_match(&self, query: &[Descriptor], k: usize) -> Vec<Vec<NativeMatch>> {
+        self.knn_match_filtered(query, k, |_, _| true)
+    }
+
+    // filter(query_index, train_index) 为 false 的配对不参与匹配，用于搜索窗口
+    pub fn knn_match_filtered<F: Fn(usize, usize) -> bool>(
+        &self,
+        query: &[Descriptor],
+        k: usize,
+        filter: F,
+    ) -> Vec<Vec<NativeMatch>> {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if has_popcnt() {
+                // 已在运行时确认 CPU 支持 popcnt
+                return unsafe { self.scan_popcnt(query, k, &filter) };
+            }
+        }
+
+        self.scan(query, k, &filter)
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "popcnt")]
+    unsafe fn scan_popcnt<F: Fn(usize, usize) -> bool>(
+        &self,
+        query: &[Descriptor],
+        k: usize,
+        filter: &F,
+    ) -> Vec<Vec<NativeMatch>> {
+        self.scan(query, k, filter)
+    }
+
+    #[inline(always)]
+    fn scan<F: Fn(usize, usize) -> bool>(
+        &self,
+        query: &[Descriptor],
+        k: usize,
+        filter: &F,
+    ) -> Vec<Vec<NativeMatch>> {
+        query
+            .iter()
+            .enumerate()
+            .map(|(query_index, descriptor)| {
+                let descriptor = pack(descriptor);
+                let mut best = Vec::with_capacity(k + 1);
+                if k == 0 {
+                    return best;
+                }
+                for (train_index, train) in self.train.iter().enumerate() {
+                    if filter(query_index, train_index) {
+                        push_candidate(
+                            &mut best,
+                            k,
+                            NativeMatch {
+                                query_index,
+                                train_index,
+                                distance: distance(&descriptor, train),
+                            },
+                        );
+                    }
+                }
+                best
+            })
+            .collect()
+    }
+}
+
+// 采样位及对应的哈希桶
+type LshTable = (Vec<usize>, HashMap<u32, Vec<usize>>);
+
+#[derive(Copy, Clone)]
+pub struct LshConfig {
+    pub tables_count: usize,
+    // 每个哈希键采样的位数
+    pub key_size: usize,
+    // 0 只查询原桶，1 额外查询键翻转一位的相邻桶
+    pub probe_level: usize,
+    pub seed: u64,
+}
+
+pub struct LshIndex {
+    config: LshConfig,
+    train: Vec<Packed>,
+    tables: Vec<LshTable>,
+}
+
+impl Default for LshConfig {
+    fn default() -> Self {
+        Self {
+            tables_count: 8,
+            key_size: 16,
+            probe_level: 1,
+            seed: 0x9e37_79b9_7f4a_7c15,
+        }
+    }
+}
+
+impl LshIndex {
+    pub fn new(config: LshConfig, train: &[Descriptor]) -> Self {
+        let key_size = config.key_size.clamp(1, 32);
+        let mut state = config.seed | 1;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        let train = train.iter().map(pack).collect::<Vec<Packed>>();
+        let tables = (0..config.tables_count)
+            .map(|_| {
+                // 每张表随机选取互不相同的位
+                let mut bits = Vec::with_capacity(key_size);
+                while bits.len() < key_size {
+                    let bit = (next() % 256) as usize;
+                    if !bits.contains(&bit) {
+                        bits.push(bit);
+                    }
+                }
+
+                let mut buckets = HashMap::<u32, Vec<usize>>::new();
+                for (i, descriptor) in train.iter().enumerate() {
+                    buckets.entry(hash(&bits, descriptor)).or_default().push(i);
+                }
+                (bits, buckets)
+            })
+            .collect();
+
+        Self {
+            config: LshConfig { key_size, ..config },
+            train,
+            tables,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.train.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.train.is_empty()
+    }
+
+    // 近似 knn，召回率取决于表数、键长和探测级别
+    pub fn knn_match(&self, query: &[Descriptor], k: usize) -> Vec<Vec<NativeMatch>> {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if has_popcnt() {
+                // 已在运行时确认 CPU 支持 popcnt
+                return unsafe { self.scan_popcnt(query, k) };
+            }
+        }
+
+        self.scan(query, k)
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "popcnt")]
+    unsafe fn scan_popcnt(&self, query: &[Descriptor], k: usize) -> Vec<Vec<NativeMatch>> {
+        self.scan(query, k)
+    }
+
+    #[inline(always)]
+    fn scan(&self, query: &[Descriptor], k: usize) -> Vec<Vec<NativeMatch>> {
+        let mut visited = vec![usize::MAX; self.train.len()];
+
+        query
+            .iter()
+            .enumerate()
+            .map(|(query_index, descriptor)| {
+                let descriptor = pack(descriptor);
+                let mut best = Vec::with_capacity(k + 1);
+                if k == 0 {
+                    return best;
+                }
+
+                let mut visit = |bucket: Option<&Vec<usize>>| {
+                    for &train_index in bucket.into_iter().flatten() {
+                        if visited[train_index] == query_index {
+                            continue;
+                        }
+                        visited[train_index] = query_index;
+                        push_candidate(
+                            &mut best,
+                            k,
+                            NativeMatch {
+                                query_index,
+                                train_index,
+                                distance: distance(&descriptor, &self.train[train_index]),
+                            },
+                        );
+                    }
+                };
+
+                for (bits, buckets) in self.tables.iter() {
+                    let key = hash(bits, &descriptor);
+                    visit(buckets.get(&key));
+                    if self.config.probe_level > 0 {
+                        for i in 0..bits.len() {
+                            visit(buckets.get(&(key ^ (1 << i))));
+                        }
+                    }
+                }
+
+                best
+            })
+            .collect()
+    }
+}
+
+fn hash(bits: &[usize], descriptor: &Packed) -> u32 {
+    bits.iter().enumerate().fold(0, |key, (i, &bit)| {
+        key | ((((descriptor[bit / 64] >> (bit % 64)) & 1) as u32) << i)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn test() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let train = (0..500)
+            .map(|_| {
+                let mut d = [0u8; 32];
+                rng.fill(&mut d);
+                d
+            })
+            .collect::<Vec<Descriptor>>();
+
+        // 每个查询由训练描述子翻转少量位得到
+        let query = train
+            .iter()
+            .take(100)
+            .map(|d| {
+                let mut d = *d;
+                for _ in 0..8 {
+                    let bit = rng.gen_range(0..256);
+                    d[bit / 8] ^= 1 << (bit % 8);
+                }
+                d
+            })
+            .collect::<Vec<Descriptor>>();
+
+        let mut a = [0u8; 32];
+        a[0] = 0b1011;
+        a[31] = 0xff;
+        assert_eq!(hamming_distance(&a, &[0u8; 32]), 11);
+
+        let bf = HammingMatcher::new(&train);
+        let matches = bf.knn_match(&query, 2);
+        for (i, knn) in matches.iter().enumerate() {
+            assert_eq!(knn.len(), 2);
+            assert_eq!(knn[0].train_index, i);
+            assert!(knn[0].distance <= 8);
+            assert!(knn[0].distance <= knn[1].distance);
+        }
+
+        let filtered = bf.knn_match_filtered(&query, 1, |q, t| q != t);
+        assert!(filtered
+            .iter()
+            .enumerate()
+            .all(|(i, knn)| knn[0].train_index != i));
+
+        let lsh = LshIndex::new(LshConfig::default(), &train);
+        let found = lsh
+            .knn_match(&query, 1)
+            .iter()
+            .enumerate()
+            .filter(|(i, knn)| knn.first().map(|m| m.train_index) == Some(*i))
+            .count();
+        assert!(found >= 95);
+    }
+}
@@ -0,0 +1,117 @@
+use crate::{Error, ErrorKind, Result};
+
+// 行优先存储的 8 位灰度图像
+#[derive(Clone)]
+pub struct GrayImage {
+    width: usize,
+    height: usize,
+    data: Vec<u8>,
+}
+
+impl GrayImage {
+    pub fn new(width: usize, height: usize, data: Vec<u8>) -> Result<Self> {
+        if data.len() == width * height {
+            Ok(Self {
+                width,
+                height,
+                data,
+            })
+        } else {
+            Err(Error::from(ErrorKind::InvalidInput))
+        }
+    }
+
+    pub fn get_width(&self) -> usize {
+        self.width
+    }
+
+    pub fn get_height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get_data(&self) -> &[u8] {
+        &self.data
+    }
+
+    #[inline]
+    pub fn get(&self, x: usize, y: usize) -> u8 {
+        self.data[y * self.width + x]
+    }
+
+    // 越界时取最近的边缘像素
+    #[inline]
+    pub fn get_clamped(&self, x: i32, y: i32) -> u8 {
+        let x = x.max(0).min(self.width as i32 - 1) as usize;
+        let y = y.max(0).min(self.height as i32 - 1) as usize;
+        self.get(x, y)
+    }
+
+    // 双线性插值缩放，像素中心对齐
+    pub fn resize(&self, width: usize, height: usize) -> Self {
+        let scale_x = self.width as f32 / width as f32;
+        let scale_y = self.height as f32 / height as f32;
+
+        let mut data = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let sy = ((y as f32 + 0.5) * scale_y - 0.5).max(0.0);
+            let y_0 = sy.floor() as i32;
+            let fy = sy - y_0 as f32;
+            for x in 0..width {
+                let sx = ((x as f32 + 0.5) * scale_x - 0.5).max(0.0);
+                let x_0 = sx.floor() as i32;
+                let fx = sx - x_0 as f32;
+
+                let v = self.get_clamped(x_0, y_0) as f32 * (1.0 - fx) * (1.0 - fy)
+                    + self.get_clamped(x_0 + 1, y_0) as f32 * fx * (1.0 - fy)
+                    + self.get_clamped(x_0, y_0 + 1) as f32 * (1.0 - fx) * fy
+                    + self.get_clamped(x_0 + 1, y_0 + 1) as f32 * fx * fy;
+                data.push(v.round().min(255.0) as u8);
+            }
+        }
+
+        Self {
+            width,
+            height,
+            data,
+        }
+    }
+
+    // 5 阶二项式核平滑
+    pub fn smooth(&self) -> Self {
+        const KERNEL: [u32; 5] = [1, 4, 6, 4, 1];
+
+        let filter = |get: &dyn Fn(i32) -> u8| {
+            let sum = KERNEL
+                .iter()
+                .enumerate()
+                .map(|(k, w)| w * get(k as i32 - 2) as u32)
+                .sum::<u32>();
+            ((sum + 8) / 16) as u8
+        };
+
+        let mut horizontal = Vec::with_capacity(self.data.len());
+        for y in 0..self.height as i32 {
+            for x in 0..self.width as i32 {
+                horizontal.push(filter(&|d| self.get_clamped(x + d, y)));
+            }
+        }
+        let horizontal = Self {
+            width: self.width,
+            height: self.height,
+            data: horizontal,
+        };
+
+        let mut data = Vec::with_capacity(self.data.len());
+        for y in 0..self.height as i32 {
+            for x in 0..self.width as i32 {
+                data.push(filter(&|d| horizontal.get_clamped(x, y + d)));
+            }
+        }
+
+        Self {
+            width: self.width,
+            height: self.height,
+            data,
+        }
+    }
+}
@@ -0,0 +1,15 @@
+// 不依赖 OpenCV 的 FAST / ORB 实现，通过 native feature 启用，
+// 与 Mat / KeyPoint 的转换及 FeatureExtractor 后端另需 opencv feature
+#[cfg(feature = "opencv")]
+mod backend;
+mod fast;
+mod hamming;
+mod image;
+mod orb;
+
+#[cfg(feature = "opencv")]
+pub use backend::*;
+pub use fast::*;
+pub use hamming::*;
+pub use image::*;
+pub use orb::*;
@@ -0,0 +1,352 @@
+use std::cmp::Ordering;
+use std::f32::consts::PI;
+
+use super::*;
+
+// 方向与描述子采样的圆形邻域半径
+const PATCH_RADIUS: i32 = 15;
+// 旋转后的采样点不超出图像所需的边界
+const DESCRIPTOR_BORDER: usize = 22;
+const HARRIS_BLOCK_RADIUS: i32 = 3;
+
+pub type Descriptor = [u8; 32];
+
+#[derive(Copy, Clone, Debug)]
+pub struct NativeKeyPoint {
+    // 原始分辨率下的坐标
+    pub x: f32,
+    pub y: f32,
+    pub size: f32,
+    // 角度制，与 OpenCV 一致
+    pub angle: f32,
+    pub response: f32,
+    pub octave: i32,
+}
+
+pub struct NativeFeatures {
+    pub keypoints: Vec<NativeKeyPoint>,
+    pub descriptors: Vec<Descriptor>,
+}
+
+#[derive(Copy, Clone)]
+pub struct NativeOrbConfig {
+    pub features_count: usize,
+    pub scale_factor: f32,
+    pub levels_count: usize,
+    pub edge_threshold: usize,
+    pub fast_threshold: u8,
+}
+
+pub struct NativeOrb {
+    config: NativeOrbConfig,
+    pattern: Vec<[(i32, i32); 2]>,
+}
+
+impl Default for NativeOrbConfig {
+    fn default() -> Self {
+        Self {
+            features_count: 500,
+            scale_factor: 1.2,
+            levels_count: 8,
+            edge_threshold: 31,
+            fast_threshold: 20,
+        }
+    }
+}
+
+impl NativeOrb {
+    pub fn new(config: NativeOrbConfig) -> Self {
+        Self {
+            config,
+            pattern: generate_pattern(),
+        }
+    }
+
+    pub fn detect_and_compute(
+        &self,
+        image: &GrayImage,
+        mask: Option<&GrayImage>,
+    ) -> NativeFeatures {
+        let pyramid = self.build_pyramid(image);
+        let mut keypoints = self.detect_in_pyramid(&pyramid, mask);
+        let descriptors = self.compute_in_pyramid(&pyramid, &mut keypoints);
+
+        NativeFeatures {
+            keypoints,
+            descriptors,
+        }
+    }
+
+    pub fn detect(&self, image: &GrayImage, mask: Option<&GrayImage>) -> Vec<NativeKeyPoint> {
+        self.detect_in_pyramid(&self.build_pyramid(image), mask)
+    }
+
+    // 重新计算方向，并剔除靠近边界无法计算描述子的特征点
+    pub fn compute(
+        &self,
+        image: &GrayImage,
+        keypoints: &mut Vec<NativeKeyPoint>,
+    ) -> Vec<Descriptor> {
+        self.compute_in_pyramid(&self.build_pyramid(image), keypoints)
+    }
+
+    fn get_border(&self) -> usize {
+        self.config.edge_threshold.max(DESCRIPTOR_BORDER)
+    }
+
+    fn build_pyramid(&self, image: &GrayImage) -> Vec<(f32, GrayImage)> {
+        let mut pyramid = vec![(1.0, image.clone())];
+        for level in 1..self.config.levels_count {
+            let scale = self.config.scale_factor.powi(level as i32);
+            let width = (image.get_width() as f32 / scale).round() as usize;
+            let height = (image.get_height() as f32 / scale).round() as usize;
+            if width <= 2 * self.get_border() || height <= 2 * self.get_border() {
+                break;
+            }
+            pyramid.push((scale, image.resize(width, height)));
+        }
+
+        pyramid
+    }
+
+    fn detect_in_pyramid(
+        &self,
+        pyramid: &[(f32, GrayImage)],
+        mask: Option<&GrayImage>,
+    ) -> Vec<NativeKeyPoint> {
+        // 各层特征点数按面积比例分配
+        let factor = 1.0 / self.config.scale_factor;
+        let levels_count = pyramid.len() as i32;
+        let mut features_per_level =
+            self.config.features_count as f32 * (1.0 - factor) / (1.0 - factor.powi(levels_count));
+
+        // 与 OpenCV 一致，空掩码视为不使用掩码
+        let mask = mask.filter(|mask| mask.get_width() > 0 && mask.get_height() > 0);
+
+        let mut keypoints = Vec::new();
+        for (level, (scale, level_image)) in pyramid.iter().enumerate() {
+            let mut candidates =
+                fast_detect(level_image, self.config.fast_threshold, self.get_border())
+                    .into_iter()
+                    .filter(|(x, y, _)| {
+                        mask.map(|mask| {
+                            let mx = ((*x as f32 * scale) as usize).min(mask.get_width() - 1);
+                            let my = ((*y as f32 * scale) as usize).min(mask.get_height() - 1);
+                            mask.get(mx, my) != 0
+                        })
+                        .unwrap_or(true)
+                    })
+                    .map(|(x, y, _)| {
+                        (
+                            x,
+                            y,
+                            harris_response(level_image, x, y, HARRIS_BLOCK_RADIUS),
+                        )
+                    })
+                    .collect::<Vec<(usize, usize, f32)>>();
+
+            candidates.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap_or(Ordering::Equal));
+            candidates.truncate(features_per_level.round() as usize);
+
+            keypoints.extend(
+                candidates
+                    .into_iter()
+                    .map(|(x, y, response)| NativeKeyPoint {
+                        x: x as f32 * scale,
+                        y: y as f32 * scale,
+                        size: (2 * PATCH_RADIUS + 1) as f32 * scale,
+                        angle: -1.0,
+                        response,
+                        octave: level as i32,
+                    }),
+            );
+
+            features_per_level *= factor;
+        }
+
+        keypoints
+    }
+
+    fn compute_in_pyramid(
+        &self,
+        pyramid: &[(f32, GrayImage)],
+        keypoints: &mut Vec<NativeKeyPoint>,
+    ) -> Vec<Descriptor> {
+        let smoothed = pyramid
+            .iter()
+            .map(|(_, level_image)| level_image.smooth())
+            .collect::<Vec<GrayImage>>();
+
+        let border = DESCRIPTOR_BORDER as i32;
+        let mut descriptors = Vec::with_capacity(keypoints.len());
+        let mut retained = Vec::with_capacity(keypoints.len());
+        for mut kp in keypoints.drain(..) {
+            let level = (kp.octave.max(0) as usize).min(pyramid.len() - 1);
+            let (scale, level_image) = &pyramid[level];
+            let x = (kp.x / scale).round() as i32;
+            let y = (kp.y / scale).round() as i32;
+            if x < border
+                || y < border
+                || x >= level_image.get_width() as i32 - border
+                || y >= level_image.get_height() as i32 - border
+            {
+                continue;
+            }
+
+            let angle = ic_angle(level_image, x, y);
+            kp.angle = (angle.to_degrees() + 360.0) % 360.0;
+            descriptors.push(self.describe(&smoothed[level], x, y, angle));
+            retained.push(kp);
+        }
+        *keypoints = retained;
+
+        descriptors
+    }
+
+    fn describe(&self, image: &GrayImage, x: i32, y: i32, angle: f32) -> Descriptor {
+        let (sin, cos) = angle.sin_cos();
+        let sample = |(px, py): (i32, i32)| {
+            let (px, py) = (px as f32, py as f32);
+            let rx = (cos * px - sin * py).round() as i32;
+            let ry = (sin * px + cos * py).round() as i32;
+            image.get((x + rx) as usize, (y + ry) as usize)
+        };
+
+        let mut descriptor = [0u8; 32];
+        for (i, [p, q]) in self.pattern.iter().enumerate() {
+            if sample(*p) < sample(*q) {
+                descriptor[i / 8] |= 1 << (i % 8);
+            }
+        }
+
+        descriptor
+    }
+}
+
+// 灰度质心法计算方向，返回弧度
+fn ic_angle(image: &GrayImage, x: i32, y: i32) -> f32 {
+    let (mut m_01, mut m_10) = (0.0f32, 0.0f32);
+    for dy in -PATCH_RADIUS..=PATCH_RADIUS {
+        for dx in -PATCH_RADIUS..=PATCH_RADIUS {
+            if dx * dx + dy * dy <= PATCH_RADIUS * PATCH_RADIUS {
+                let v = image.get((x + dx) as usize, (y + dy) as usize) as f32;
+                m_10 += dx as f32 * v;
+                m_01 += dy as f32 * v;
+            }
+        }
+    }
+
+    m_01.atan2(m_10)
+}
+
+// 固定种子生成 256 对高斯分布的采样点（BRIEF G II），保证描述子可复现
+fn generate_pattern() -> Vec<[(i32, i32); 2]> {
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    let mut uniform = || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        ((state >> 11) as f64 / (1u64 << 53) as f64) as f32
+    };
+
+    let sigma = (2 * PATCH_RADIUS + 1) as f32 / 5.0;
+    let mut gaussian = || {
+        // Box-Muller
+        let u_0 = uniform().max(f32::MIN_POSITIVE);
+        let u_1 = uniform();
+        let v = (-2.0 * u_0.ln()).sqrt() * (2.0 * PI * u_1).cos() * sigma;
+        (v.round() as i32).clamp(-PATCH_RADIUS, PATCH_RADIUS)
+    };
+
+    (0..256)
+        .map(|_| [(gaussian(), gaussian()), (gaussian(), gaussian())])
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+
+    fn random_image(width: usize, height: usize) -> GrayImage {
+        let mut rng = StdRng::seed_from_u64(0);
+        let noise = GrayImage::new(
+            width,
+            height,
+            (0..width * height).map(|_| rng.gen()).collect(),
+        )
+        .unwrap();
+        noise.smooth()
+    }
+
+    #[test]
+    fn test() {
+        let image = random_image(320, 240);
+        let orb = NativeOrb::new(NativeOrbConfig::default());
+
+        let features = orb.detect_and_compute(&image, None);
+        assert!(!features.keypoints.is_empty());
+        assert!(features.keypoints.len() <= 500);
+        assert_eq!(features.keypoints.len(), features.descriptors.len());
+
+        // 空掩码与不使用掩码一致
+        let empty = GrayImage::new(0, 0, Vec::new()).unwrap();
+        assert_eq!(
+            orb.detect(&image, Some(&empty)).len(),
+            orb.detect(&image, None).len()
+        );
+
+        // 平移整数像素后，第 0 层特征点的描述子不变
+        let (shift_x, shift_y) = (8, 5);
+        let shifted = GrayImage::new(
+            image.get_width() - shift_x,
+            image.get_height() - shift_y,
+            (0..(image.get_height() - shift_y))
+                .flat_map(|y| {
+                    let image = &image;
+                    (0..(image.get_width() - shift_x))
+                        .map(move |x| image.get(x + shift_x, y + shift_y))
+                })
+                .collect(),
+        )
+        .unwrap();
+
+        let mut level_0 = features
+            .keypoints
+            .iter()
+            .filter(|kp| kp.octave == 0)
+            .map(|kp| NativeKeyPoint {
+                x: kp.x - shift_x as f32,
+                y: kp.y - shift_y as f32,
+                ..*kp
+            })
+            .collect::<Vec<NativeKeyPoint>>();
+        let expected = features
+            .keypoints
+            .iter()
+            .zip(features.descriptors.iter())
+            .filter(|(kp, _)| kp.octave == 0)
+            .map(|(_, d)| *d)
+            .collect::<Vec<Descriptor>>();
+        let count = level_0.len();
+        let descriptors = orb.compute(&shifted, &mut level_0);
+
+        // 只比较两幅图中都远离边界的特征点
+        assert!(level_0.len() <= count);
+        let matched = level_0
+            .iter()
+            .zip(descriptors.iter())
+            .filter(|(kp, d)| {
+                features
+                    .keypoints
+                    .iter()
+                    .zip(expected.iter())
+                    .any(|(e, ed)| {
+                        e.x - shift_x as f32 == kp.x && e.y - shift_y as f32 == kp.y && ed == *d
+                    })
+            })
+            .count();
+        assert!(matched as f64 >= 0.9 * level_0.len() as f64);
+    }
+}
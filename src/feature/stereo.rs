@@ -0,0 +1,252 @@
+use opencv::core::*;
+
+use super::*;
+
+pub struct StereoConfig {
+    // 校正后的焦距与基线
+    pub focal_length: f64,
+    pub baseline: f64,
+    pub max_row_diff: f64,
+    pub min_disparity: f64,
+    pub max_disparity: f64,
+    // 为 None 时按描述子距离类型取 default_descriptor_distance
+    pub max_descriptor_distance: Option<f64>,
+    // 亚像素精化的 SAD 窗口半径与搜索范围
+    pub patch_radius: i32,
+    pub search_range: i32,
+}
+
+// 在校正后的左右图像间沿极线（同一行）匹配特征点，输出左图特征点的深度
+pub struct StereoMatcher {
+    config: StereoConfig,
+}
+
+impl StereoConfig {
+    pub fn new(focal_length: f64, baseline: f64) -> Self {
+        Self {
+            focal_length,
+            baseline,
+            max_row_diff: 2.0,
+            min_disparity: 1.0,
+            max_disparity: 128.0,
+            max_descriptor_distance: None,
+            patch_radius: 5,
+            search_range: 5,
+        }
+    }
+}
+
+impl StereoMatcher {
+    pub fn new(focal_length: f64, baseline: f64) -> Self {
+        Self::with_config(StereoConfig::new(focal_length, baseline))
+    }
+
+    pub fn with_config(config: StereoConfig) -> Self {
+        Self { config }
+    }
+
+    // 返回值与左图特征点一一对应，即与 Matcher::process(left) 输出的顺序一致
+    pub fn process(
+        &self,
+        left: &Features,
+        right: &Features,
+        left_image: &Mat,
+        right_image: &Mat,
+    ) -> Vec<Option<f64>> {
+        let max_descriptor_distance = self
+            .config
+            .max_descriptor_distance
+            .unwrap_or_else(|| default_descriptor_distance(left.descriptor_norm));
+
+        // 按行建立右图特征点索引
+        let rows = right_image.rows().max(0) as usize;
+        let mut row_indexes = vec![Vec::new(); rows];
+        for (j, kp) in right.keypoints.iter().enumerate() {
+            let y_0 = (kp.pt.y as f64 - self.config.max_row_diff).floor().max(0.0) as usize;
+            let y_1 = (kp.pt.y as f64 + self.config.max_row_diff).ceil() as usize;
+            for row in row_indexes.iter_mut().take(y_1 + 1).skip(y_0) {
+                row.push(j);
+            }
+        }
+
+        left.keypoints
+            .iter()
+            .enumerate()
+            .map(|(i, left_kp)| {
+                let row = left_kp.pt.y.round() as usize;
+                let left_descriptor = left.descriptors.row(i as i32).unwrap();
+
+                let mut best: Option<(usize, f64)> = None;
+                for j in row_indexes.get(row).into_iter().flatten() {
+                    let right_kp = right.keypoints.get(*j).unwrap();
+                    let disparity = (left_kp.pt.x - right_kp.pt.x) as f64;
+                    if disparity < self.config.min_disparity
+                        || disparity > self.config.max_disparity
+                    {
+                        continue;
+                    }
+
+                    let distance = norm2(
+                        &left_descriptor,
+                        &right.descriptors.row(*j as i32).unwrap(),
+                        left.descriptor_norm,
+                        &no_array().unwrap(),
+                    )
+                    .unwrap();
+                    if distance < max_descriptor_distance
+                        && best.map(|(_, d)| distance < d).unwrap_or(true)
+                    {
+                        best = Some((*j, distance));
+                    }
+                }
+
+                best.and_then(|(j, _)| {
+                    let right_kp = right.keypoints.get(j).unwrap();
+                    self.refine_right_x(left_image, right_image, &left_kp.pt, right_kp.pt.x)
+                        .map(|right_x| left_kp.pt.x as f64 - right_x)
+                        .filter(|disparity| *disparity >= self.config.min_disparity)
+                        .map(|disparity| {
+                            self.config.focal_length * self.config.baseline / disparity
+                        })
+                })
+            })
+            .collect()
+    }
+
+    // SAD 块匹配后抛物线拟合得到亚像素位置
+    fn refine_right_x(
+        &self,
+        left_image: &Mat,
+        right_image: &Mat,
+        left_pt: &Point2f,
+        right_x: f32,
+    ) -> Option<f64> {
+        let w = self.config.patch_radius;
+        let l = self.config.search_range;
+        let (x_l, y) = (left_pt.x.round() as i32, left_pt.y.round() as i32);
+        let x_r = right_x.round() as i32;
+
+        if y - w < 0
+            || y + w >= left_image.rows()
+            || x_l - w < 0
+            || x_l + w >= left_image.cols()
+            || x_r - w - l - 1 < 0
+            || x_r + w + l + 1 >= right_image.cols()
+        {
+            return None;
+        }
+
+        // 以中心像素归一化，降低左右曝光差异的影响
+        let left_center = *left_image.at_2d::<u8>(y, x_l).unwrap() as i32;
+        let sad = |x: i32| {
+            let right_center = *right_image.at_2d::<u8>(y, x).unwrap() as i32;
+            let mut sum = 0;
+            for dy in -w..=w {
+                for dx in -w..=w {
+                    let lv = *left_image.at_2d::<u8>(y + dy, x_l + dx).unwrap() as i32;
+                    let rv = *right_image.at_2d::<u8>(y + dy, x + dx).unwrap() as i32;
+                    sum += ((lv - left_center) - (rv - right_center)).abs();
+                }
+            }
+            sum as f64
+        };
+
+        let sads = (-l - 1..=l + 1).map(|d| sad(x_r + d)).collect::<Vec<f64>>();
+        let (best_index, _) =
+            sads[1..sads.len() - 1]
+                .iter()
+                .enumerate()
+                .fold(
+                    (0, f64::MAX),
+                    |(bi, bv), (i, v)| {
+                        if *v < bv {
+                            (i, *v)
+                        } else {
+                            (bi, bv)
+                        }
+                    },
+                );
+        let best_index = best_index + 1;
+
+        let (d_0, d_1, d_2) = (sads[best_index - 1], sads[best_index], sads[best_index + 1]);
+        let denominator = 2.0 * (d_0 + d_2 - 2.0 * d_1);
+        let delta = if denominator > 0.0 {
+            (d_0 - d_2) / denominator
+        } else {
+            0.0
+        };
+        if delta.abs() > 1.0 {
+            return None;
+        }
+
+        Some((x_r + best_index as i32 - l - 1) as f64 + delta)
+    }
+}
+
+pub fn assign_depths(matched_features: &mut [MatchedFeature], depths: &[Option<f64>]) {
+    matched_features
+        .iter_mut()
+        .zip(depths.iter())
+        .for_each(|(mf, depth)| mf.depth = *depth);
+}
+
+#[cfg(test)]
+mod test {
+    use opencv::imgproc::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+
+    #[async_std::test]
+    async fn test() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut noise =
+            Mat::new_rows_cols_with_default(480, 660, CV_8U, opencv::core::Scalar::all(0.0))
+                .unwrap();
+        for y in 0..480 {
+            for x in 0..660 {
+                *noise.at_2d_mut::<u8>(y, x).unwrap() = rng.gen();
+            }
+        }
+        let mut texture = Mat::default().unwrap();
+        gaussian_blur(
+            &noise,
+            &mut texture,
+            Size::new(5, 5),
+            1.5,
+            1.5,
+            BORDER_DEFAULT,
+        )
+        .unwrap();
+
+        // 右图相对左图整体左移 10 像素
+        let mut left_image = Mat::default().unwrap();
+        let mut right_image = Mat::default().unwrap();
+        Mat::roi(&texture, Rect::new(10, 0, 640, 480))
+            .unwrap()
+            .copy_to(&mut left_image)
+            .unwrap();
+        Mat::roi(&texture, Rect::new(20, 0, 640, 480))
+            .unwrap()
+            .copy_to(&mut right_image)
+            .unwrap();
+
+        // 浮点描述子使用按 L2 距离选取的阈值
+        for backend in [ExtractorBackend::Orb, ExtractorBackend::Sift].iter() {
+            let mut extractor = Extractor::with_config(&ExtractorConfig {
+                backend: *backend,
+                fast_threshold: 20,
+                ..ExtractorConfig::default()
+            });
+            let left = extractor.get_features(&left_image).await.unwrap();
+            let right = extractor.get_features(&right_image).await.unwrap();
+
+            let stereo_matcher = StereoMatcher::new(700.0, 0.5);
+            let depths = stereo_matcher.process(&left, &right, &left_image, &right_image);
+            let valid = depths.iter().flatten().collect::<Vec<&f64>>();
+            assert!(!valid.is_empty());
+            let good_count = valid.iter().filter(|d| (***d - 35.0).abs() < 1.0).count();
+            assert!(good_count as f64 >= 0.9 * valid.len() as f64);
+        }
+    }
+}
@@ -4,12 +4,20 @@ use std::time::{Duration, SystemTime};
 
 use nalgebra::*;
 
+#[cfg(feature = "opencv")]
 pub mod estimation;
 pub mod feature;
+#[cfg(feature = "opencv")]
+pub mod map;
+#[cfg(feature = "opencv")]
+pub mod preprocess;
+#[cfg(feature = "opencv")]
 pub mod source;
+#[cfg(feature = "opencv")]
 pub mod track;
 pub mod utils;
 
+#[derive(Copy, Clone, Debug)]
 pub struct RnT {
     pub position_diff: Vector3<f64>,
     pub orientation_diff: Quaternion<f64>,
@@ -37,7 +45,7 @@ pub fn seconds_to_timestamp(time: f64) -> SystemTime {
     SystemTime::UNIX_EPOCH + Duration::from_secs_f64(time)
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "opencv"))]
 mod test {
     use nalgebra::*;
 
@@ -63,7 +71,7 @@ mod test {
         'a: loop {
             match dataset_reader.read_next().await {
                 Ok((time, _, img)) => {
-                    let features = feature_extractor.get_features(&img).await;
+                    let features = feature_extractor.get_features(&img).await.unwrap();
                     let matched_features = matcher.process(features).await;
                     tracker.update_matched(&time, &matched_features);
                     let tracked = tracker.get_tracked();
@@ -0,0 +1,410 @@
+use std::collections::HashMap;
+
+use nalgebra::*;
+
+use super::*;
+use crate::utils::ConfigFile;
+use crate::*;
+
+#[derive(Copy, Clone)]
+pub struct MapConfig {
+    // 首次三角化所需的最小视线夹角，弧度
+    pub min_parallax: f64,
+    // 各观测的最大重投影误差，像素
+    pub max_reprojection_error: f64,
+    // 首次三角化所需的已知位姿观测数
+    pub min_observations: usize,
+}
+
+// 由轨迹三角化得到的路标点，ID 与轨迹 ID 相同
+#[derive(Clone)]
+pub struct Landmark {
+    id: u64,
+    position: Vector3<f64>,
+    // (帧号, 像素坐标)，按帧号从旧到新
+    observations: Vec<(u64, Vector2<f64>)>,
+    descriptor: Option<Vec<u8>>,
+    reprojection_error: f64,
+}
+
+pub struct LocalMap {
+    config: MapConfig,
+    camera_matrix: Matrix3<f64>,
+    // 各帧世界坐标到相机坐标的变换 X_c = R·X_w + t
+    poses: HashMap<u64, RnT>,
+    landmarks: HashMap<u64, Landmark>,
+}
+
+impl MapConfig {
+    pub fn from_config_file(config: &ConfigFile) -> Result<Self> {
+        let default = Self::default();
+
+        Ok(Self {
+            min_parallax: config.get_or("map.min_parallax", default.min_parallax)?,
+            max_reprojection_error: config
+                .get_or("map.max_reprojection_error", default.max_reprojection_error)?,
+            min_observations: config.get_or("map.min_observations", default.min_observations)?,
+        })
+    }
+}
+
+impl Default for MapConfig {
+    fn default() -> Self {
+        Self {
+            min_parallax: 1.0f64.to_radians(),
+            max_reprojection_error: 2.0,
+            min_observations: 2,
+        }
+    }
+}
+
+impl Landmark {
+    pub fn get_id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn get_position(&self) -> Vector3<f64> {
+        self.position
+    }
+
+    pub fn get_observations(&self) -> &[(u64, Vector2<f64>)] {
+        &self.observations
+    }
+
+    pub fn get_descriptor(&self) -> Option<&[u8]> {
+        self.descriptor.as_deref()
+    }
+
+    // 三角化时各观测中的最大重投影误差
+    pub fn get_reprojection_error(&self) -> f64 {
+        self.reprojection_error
+    }
+
+    // 观测均已超出跟踪窗口时为 None
+    fn get_last_frame(&self) -> Option<u64> {
+        self.observations.last().map(|(frame_id, _)| *frame_id)
+    }
+}
+
+impl LocalMap {
+    pub fn new(camera_matrix: Matrix3<f64>) -> Self {
+        Self::with_config(camera_matrix, MapConfig::default())
+    }
+
+    pub fn with_config(camera_matrix: Matrix3<f64>, config: MapConfig) -> Self {
+        Self {
+            config,
+            camera_matrix,
+            poses: HashMap::new(),
+            landmarks: HashMap::new(),
+        }
+    }
+
+    pub fn set_pose(&mut self, frame_id: u64, pose: RnT) {
+        self.poses.insert(frame_id, pose);
+    }
+
+    pub fn get_pose(&self, frame_id: u64) -> Option<&RnT> {
+        self.poses.get(&frame_id)
+    }
+
+    pub fn get_landmark(&self, id: u64) -> Option<&Landmark> {
+        self.landmarks.get(&id)
+    }
+
+    pub fn landmarks(&self) -> impl Iterator<Item = &Landmark> {
+        self.landmarks.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.landmarks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.landmarks.is_empty()
+    }
+
+    pub fn remove_landmarks(&mut self, ids: &[u64]) {
+        for id in ids {
+            self.landmarks.remove(id);
+        }
+    }
+
+    // 丢弃全部位姿与路标点，用于尺度不一致时重新初始化
+    pub fn clear(&mut self) {
+        self.poses.clear();
+        self.landmarks.clear();
+    }
+
+    // 三角化视差足够的新轨迹，并将已有路标点的新观测加入，返回新增路标点数
+    // 已结束的轨迹同样参与，超出跟踪窗口的位姿及只在这些帧中观测到的路标点随之丢弃
+    pub fn update(&mut self, tracker: &track::Tracker) -> usize {
+        self.prune(tracker);
+
+        let mut created = 0;
+        for track in tracker.tracks().chain(tracker.ended_tracks()) {
+            // 只使用位姿已知的观测
+            let views = track
+                .get_observations()
+                .filter_map(|o| {
+                    self.poses
+                        .get(&o.frame_id)
+                        .map(|pose| (o.frame_id, (*pose, o.point.vp_position)))
+                })
+                .collect::<Vec<(u64, View)>>();
+
+            let landmark = match self.landmarks.remove(&track.get_id()) {
+                Some(mut landmark) => {
+                    self.extend(&mut landmark, &views);
+                    Some(landmark)
+                }
+                None => {
+                    let landmark = self.triangulate(track.get_id(), views);
+                    if landmark.is_some() {
+                        created += 1;
+                    }
+                    landmark
+                }
+            };
+            if let Some(mut landmark) = landmark {
+                if let Some(descriptor) = track.get_descriptor() {
+                    landmark.descriptor = Some(descriptor.to_vec());
+                }
+                self.landmarks.insert(landmark.id, landmark);
+            }
+        }
+
+        created
+    }
+
+    fn prune(&mut self, tracker: &track::Tracker) {
+        let tracked = tracker.get_tracked();
+        if let Some(first_frame_id) = tracked
+            .frames_count()
+            .checked_sub(1)
+            .and_then(|frame_index| tracked.get_frame_id(frame_index))
+        {
+            self.poses.retain(|frame_id, _| *frame_id >= first_frame_id);
+        }
+
+        let poses = &self.poses;
+        self.landmarks.retain(|_, landmark| {
+            landmark
+                .observations
+                .retain(|(frame_id, _)| poses.contains_key(frame_id));
+            !landmark.observations.is_empty()
+        });
+    }
+
+    // 重投影误差过大时丢弃误差最大的观测后重试，直到观测数不足
+    fn triangulate(&self, id: u64, mut views: Vec<(u64, View)>) -> Option<Landmark> {
+        loop {
+            if views.len() < self.config.min_observations.max(2) {
+                return None;
+            }
+
+            // 与最早观测夹角最大的观测
+            let (best, parallax) = views[1..]
+                .iter()
+                .map(|(_, view)| (view, parallax_angle(&self.camera_matrix, &views[0].1, view)))
+                .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())?;
+            if parallax < self.config.min_parallax {
+                return None;
+            }
+
+            // 线性三角化与中点法中重投影误差较小者
+            let all_views = views.iter().map(|(_, view)| *view).collect::<Vec<View>>();
+            let solved = vec![
+                triangulate_dlt(&self.camera_matrix, &all_views),
+                triangulate_midpoint(&self.camera_matrix, &views[0].1, best),
+            ]
+            .into_iter()
+            .flatten()
+            .filter_map(|position| {
+                reprojection_error(&self.camera_matrix, &all_views, &position)
+                    .map(|error| (position, error))
+            })
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+            if let Some((position, error)) =
+                solved.filter(|(_, error)| *error <= self.config.max_reprojection_error)
+            {
+                return Some(Landmark {
+                    id,
+                    position,
+                    observations: views
+                        .iter()
+                        .map(|(frame_id, (_, p))| (*frame_id, *p))
+                        .collect(),
+                    descriptor: None,
+                    reprojection_error: error,
+                });
+            }
+
+            // 以 DLT 结果衡量各观测，位于相机后方的观测误差视为无穷大
+            let position = triangulate_dlt(&self.camera_matrix, &all_views)?;
+            let (worst, _) = all_views
+                .iter()
+                .map(|view| {
+                    reprojection_error(&self.camera_matrix, std::slice::from_ref(view), &position)
+                        .unwrap_or(f64::INFINITY)
+                })
+                .enumerate()
+                .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())?;
+            views.remove(worst);
+        }
+    }
+
+    // 加入与当前位置一致的新观测后重新三角化，返回是否加入了新观测
+    fn extend(&self, landmark: &mut Landmark, views: &[(u64, View)]) -> bool {
+        let last_frame = landmark.get_last_frame();
+        let mut extended = false;
+        for (frame_id, view) in views
+            .iter()
+            .filter(|(frame_id, _)| Some(*frame_id) > last_frame)
+        {
+            let consistent = reprojection_error(
+                &self.camera_matrix,
+                std::slice::from_ref(view),
+                &landmark.position,
+            )
+            .map(|error| error <= self.config.max_reprojection_error)
+            .unwrap_or(false);
+            if consistent {
+                landmark.observations.push((*frame_id, view.1));
+                extended = true;
+            }
+        }
+        if !extended {
+            return false;
+        }
+
+        let all_views = landmark
+            .observations
+            .iter()
+            .filter_map(|(frame_id, p)| self.poses.get(frame_id).map(|pose| (*pose, *p)))
+            .collect::<Vec<View>>();
+        if let Some(position) = triangulate_dlt(&self.camera_matrix, &all_views) {
+            if let Some(error) = reprojection_error(&self.camera_matrix, &all_views, &position) {
+                if error <= self.config.max_reprojection_error {
+                    landmark.position = position;
+                    landmark.reprojection_error = error;
+                }
+            }
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::SystemTime;
+
+    use super::*;
+    use crate::utils::test_scene::{camera_matrix, grid_landmarks};
+
+    #[test]
+    fn test() {
+        let camera_matrix = camera_matrix();
+        let mut map = LocalMap::new(camera_matrix);
+        let mut tracker = track::Tracker::new(3);
+        let time = SystemTime::now();
+
+        // 相机每帧沿 x 轴平移 0.5，最后一个点在第 1 帧偏离 20 像素，前 5 个点在第 3 帧跟丢
+        let positions = grid_landmarks(20, 5, 1.0, |i| 5.0 + i as f64 * 0.2);
+        let pose = |frame: usize| RnT {
+            position_diff: Vector3::new(-0.5 * frame as f64, 0.0, 0.0),
+            orientation_diff: Quaternion::identity(),
+        };
+        let project_frame = |frame: usize| {
+            positions
+                .iter()
+                .enumerate()
+                .map(|(i, p)| {
+                    let mut position = project(&camera_matrix, &pose(frame), p).unwrap();
+                    if frame == 1 && i == positions.len() - 1 {
+                        position.y += 20.0;
+                    }
+                    let matched = frame > 0 && !(frame == 3 && i < 5);
+                    feature::MatchedFeature {
+                        prev_index: if matched { i as u32 } else { u32::MAX },
+                        prev_frame_offset: 1,
+                        position,
+                        match_degree: if matched { 1.0 } else { 0.0 },
+                        depth: None,
+                        descriptor: Some(vec![i as u8, frame as u8]),
+                    }
+                })
+                .collect::<Vec<feature::MatchedFeature>>()
+        };
+
+        tracker.update_matched(&time, &project_frame(0));
+        map.set_pose(0, pose(0));
+        assert_eq!(map.update(&tracker), 0);
+
+        tracker.update_matched(&time, &project_frame(1));
+        map.set_pose(1, pose(1));
+        assert_eq!(map.update(&tracker), 19);
+        assert!(map.get_landmark(19).is_none());
+        for i in 0..19 {
+            let landmark = map.get_landmark(i).unwrap();
+            assert!((landmark.get_position() - positions[i as usize]).norm() < 1e-6);
+            assert_eq!(landmark.get_descriptor(), Some(&[i as u8, 1][..]));
+        }
+
+        // 轨迹延续后加入新的观测
+        tracker.update_matched(&time, &project_frame(2));
+        map.set_pose(2, pose(2));
+        assert_eq!(map.update(&tracker), 1);
+        let landmark = map.get_landmark(0).unwrap();
+        assert_eq!(landmark.get_observations().len(), 3);
+        assert_eq!(landmark.get_descriptor(), Some(&[0, 2][..]));
+        assert!((landmark.get_position() - positions[0]).norm() < 1e-6);
+        // 丢弃偏离的观测后三角化成功
+        let landmark = map.get_landmark(19).unwrap();
+        let frame_ids = landmark
+            .get_observations()
+            .iter()
+            .map(|(frame_id, _)| *frame_id)
+            .collect::<Vec<u64>>();
+        assert_eq!(frame_ids, vec![0, 2]);
+        assert!((landmark.get_position() - positions[19]).norm() < 1e-6);
+
+        // 超出窗口的第 0 帧位姿及其观测随之丢弃，跟丢的轨迹结束后路标点仍保留
+        // 已结束轨迹的路标点被移除后由历史轨迹重新三角化
+        map.remove_landmarks(&[0]);
+        tracker.update_matched(&time, &project_frame(3));
+        map.set_pose(3, pose(3));
+        assert_eq!(map.update(&tracker), 1);
+        assert!(map.get_pose(0).is_none());
+        assert_eq!(map.len(), 20);
+        assert!(tracker.get_track(0).unwrap().is_ended());
+        let landmark = map.get_landmark(0).unwrap();
+        assert_eq!(landmark.get_observations().len(), 2);
+        assert_eq!(landmark.get_observations()[0].0, 1);
+        let landmark = map.get_landmark(5).unwrap();
+        assert_eq!(landmark.get_observations().len(), 3);
+        assert_eq!(landmark.get_observations()[0].0, 1);
+
+        // 第 3 帧新建的轨迹延续后三角化
+        tracker.update_matched(&time, &project_frame(4));
+        map.set_pose(4, pose(4));
+        assert_eq!(map.update(&tracker), 5);
+        assert_eq!(map.len(), 25);
+
+        // 观测均超出窗口后丢弃路标点
+        tracker.update_matched(&time, &project_frame(5));
+        map.set_pose(5, pose(5));
+        assert_eq!(map.update(&tracker), 0);
+        assert_eq!(map.len(), 20);
+        for i in 0..5 {
+            assert!(map.get_landmark(i).is_none());
+        }
+
+        map.remove_landmarks(&[5]);
+        assert_eq!(map.len(), 19);
+        map.clear();
+        assert!(map.is_empty());
+        assert!(map.get_pose(3).is_none());
+    }
+}
@@ -0,0 +1,5 @@
+mod local_map;
+mod triangulation;
+
+pub use local_map::*;
+pub use triangulation::*;
@@ -0,0 +1,159 @@
+use nalgebra::*;
+
+use crate::RnT;
+
+// 位姿为世界坐标到相机坐标的变换 X_c = R·X_w + t
+pub type View = (RnT, Vector2<f64>);
+
+fn to_rotation(pose: &RnT) -> Matrix3<f64> {
+    UnitQuaternion::from_quaternion(pose.orientation_diff)
+        .to_rotation_matrix()
+        .into_inner()
+}
+
+// 相机坐标系下的深度，小于等于 0 时位于相机后方
+pub fn get_depth(pose: &RnT, position: &Vector3<f64>) -> f64 {
+    (to_rotation(pose) * position + pose.position_diff).z
+}
+
+// 投影到像素坐标，位于相机后方时为 None
+pub fn project(
+    camera_matrix: &Matrix3<f64>,
+    pose: &RnT,
+    position: &Vector3<f64>,
+) -> Option<Vector2<f64>> {
+    let p = to_rotation(pose) * position + pose.position_diff;
+    if p.z <= 0.0 {
+        return None;
+    }
+
+    let p = camera_matrix * p;
+    Some(Vector2::new(p.x / p.z, p.y / p.z))
+}
+
+// 各视图中的最大重投影误差，像素，任一视图未通过正深度检验时为 None
+pub fn reprojection_error(
+    camera_matrix: &Matrix3<f64>,
+    views: &[View],
+    position: &Vector3<f64>,
+) -> Option<f64> {
+    views.iter().try_fold(0.0f64, |error, (pose, p)| {
+        project(camera_matrix, pose, position).map(|q| error.max((q - p).norm()))
+    })
+}
+
+// 世界坐标系下的光心与单位视线方向
+fn to_ray(inv_camera_matrix: &Matrix3<f64>, view: &View) -> (Vector3<f64>, Vector3<f64>) {
+    let r_t = to_rotation(&view.0).transpose();
+    let center = -(r_t * view.0.position_diff);
+    let direction = (r_t * inv_camera_matrix * view.1.push(1.0)).normalize();
+    (center, direction)
+}
+
+// 两视线的夹角，弧度
+pub fn parallax_angle(camera_matrix: &Matrix3<f64>, view_0: &View, view_1: &View) -> f64 {
+    let inv_camera_matrix = match camera_matrix.try_inverse() {
+        Some(inv) => inv,
+        None => return 0.0,
+    };
+    let (_, d_0) = to_ray(&inv_camera_matrix, view_0);
+    let (_, d_1) = to_ray(&inv_camera_matrix, view_1);
+    d_0.dot(&d_1).clamp(-1.0, 1.0).acos()
+}
+
+// 多视图线性三角化，在归一化平面上构造方程以改善条件数
+pub fn triangulate_dlt(camera_matrix: &Matrix3<f64>, views: &[View]) -> Option<Vector3<f64>> {
+    if views.len() < 2 {
+        return None;
+    }
+    let inv_camera_matrix = camera_matrix.try_inverse()?;
+
+    let mut ata = Matrix4::<f64>::zeros();
+    for (pose, p) in views {
+        let x = inv_camera_matrix * p.push(1.0);
+        let mut projection = to_rotation(pose).insert_column(3, 0.0);
+        projection.set_column(3, &pose.position_diff);
+
+        for (coord, row) in [(x.x / x.z, 0), (x.y / x.z, 1)].iter() {
+            let a = projection.row(2) * *coord - projection.row(*row);
+            ata += a.transpose() * a;
+        }
+    }
+
+    // 最小特征值对应的特征向量即齐次坐标
+    let eigen = ata.symmetric_eigen();
+    let (index, _) = eigen
+        .eigenvalues
+        .iter()
+        .enumerate()
+        .min_by(|a, b| a.1.partial_cmp(b.1).unwrap())?;
+    let x = eigen.eigenvectors.column(index);
+    if x[3].abs() < 1e-12 {
+        return None;
+    }
+
+    Some(Vector3::new(x[0] / x[3], x[1] / x[3], x[2] / x[3]))
+}
+
+// 两视线公垂线的中点，视线平行或交点位于相机后方时为 None
+pub fn triangulate_midpoint(
+    camera_matrix: &Matrix3<f64>,
+    view_0: &View,
+    view_1: &View,
+) -> Option<Vector3<f64>> {
+    let inv_camera_matrix = camera_matrix.try_inverse()?;
+    let (c_0, d_0) = to_ray(&inv_camera_matrix, view_0);
+    let (c_1, d_1) = to_ray(&inv_camera_matrix, view_1);
+
+    let cos = d_0.dot(&d_1);
+    let denom = 1.0 - cos * cos;
+    if denom < 1e-12 {
+        return None;
+    }
+
+    let b = c_1 - c_0;
+    let s_0 = (d_0.dot(&b) - cos * d_1.dot(&b)) / denom;
+    let s_1 = (cos * d_0.dot(&b) - d_1.dot(&b)) / denom;
+    if s_0 <= 0.0 || s_1 <= 0.0 {
+        return None;
+    }
+
+    Some((c_0 + d_0 * s_0 + c_1 + d_1 * s_1) / 2.0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::test_scene::camera_matrix;
+
+    #[test]
+    fn test() {
+        let camera_matrix = camera_matrix();
+        let position = Vector3::new(0.5, -0.3, 6.0);
+        let views = (0..3)
+            .map(|i| {
+                let pose = RnT {
+                    position_diff: Vector3::new(-0.4 * i as f64, 0.0, 0.1 * i as f64),
+                    orientation_diff: *UnitQuaternion::from_euler_angles(0.0, 0.02 * i as f64, 0.0)
+                        .quaternion(),
+                };
+                let p = project(&camera_matrix, &pose, &position).unwrap();
+                (pose, p)
+            })
+            .collect::<Vec<View>>();
+
+        let dlt = triangulate_dlt(&camera_matrix, &views).unwrap();
+        assert!((dlt - position).norm() < 1e-6);
+        let midpoint = triangulate_midpoint(&camera_matrix, &views[0], &views[2]).unwrap();
+        assert!((midpoint - position).norm() < 1e-6);
+        assert!(reprojection_error(&camera_matrix, &views, &dlt).unwrap() < 1e-6);
+        assert!(parallax_angle(&camera_matrix, &views[0], &views[2]) > 0.1);
+
+        // 点位于相机后方
+        let behind = Vector3::new(0.0, 0.0, -1.0);
+        assert!(get_depth(&views[0].0, &behind) < 0.0);
+        assert!(reprojection_error(&camera_matrix, &views, &behind).is_none());
+        assert!(parallax_angle(&camera_matrix, &views[0], &views[0]) < 1e-6);
+        assert!(triangulate_midpoint(&camera_matrix, &views[0], &views[0]).is_none());
+    }
+}
@@ -0,0 +1,137 @@
+use nalgebra::*;
+use opencv::{core::*, imgproc::*};
+
+use crate::*;
+
+mod steps;
+
+pub use steps::*;
+
+pub trait PreprocessStep {
+    fn apply(&mut self, src: &Mat) -> Result<Mat>;
+
+    // 输出图像相对输入图像的缩放比例
+    fn get_scale(&self) -> f64 {
+        1.0
+    }
+}
+
+pub struct Preprocessor {
+    steps: Vec<Box<dyn PreprocessStep>>,
+    mask: Option<Mat>,
+    scaled_mask: Option<Mat>,
+}
+
+impl Preprocessor {
+    pub fn new() -> Self {
+        Self {
+            steps: Vec::new(),
+            mask: None,
+            scaled_mask: None,
+        }
+    }
+
+    pub fn push_step<T: PreprocessStep + 'static>(&mut self, step: T) {
+        self.steps.push(Box::new(step));
+        self.update_scaled_mask();
+    }
+
+    // 掩码与原始图像分辨率一致，非零像素为有效区域
+    pub fn set_mask(&mut self, mask: Option<Mat>) {
+        self.mask = mask;
+        self.update_scaled_mask();
+    }
+
+    // 与输出图像分辨率一致的掩码，用作特征检测掩码
+    pub fn get_mask(&self) -> Option<&Mat> {
+        self.scaled_mask.as_ref()
+    }
+
+    pub fn get_scale(&self) -> f64 {
+        self.steps.iter().map(|s| s.get_scale()).product()
+    }
+
+    pub fn scale_camera_params(&self, params: &[Matrix3x4<f64>]) -> Vec<Matrix3x4<f64>> {
+        // 按 resize 的像素中心约定缩放
+        let s = self.get_scale();
+        let c = 0.5 * s - 0.5;
+        let scale_mat = Matrix3::new(s, 0.0, c, 0.0, s, c, 0.0, 0.0, 1.0);
+
+        params.iter().map(|p| scale_mat * p).collect()
+    }
+
+    pub fn process(&mut self, src: &Mat) -> Result<Mat> {
+        let mut dst = Mat::default().unwrap();
+        src.copy_to(&mut dst).unwrap();
+        for step in self.steps.iter_mut() {
+            dst = step.apply(&dst)?;
+        }
+
+        Ok(dst)
+    }
+
+    // 逐步缩放，与图像在每一步的取整保持一致
+    fn update_scaled_mask(&mut self) {
+        let steps = &self.steps;
+        self.scaled_mask = self.mask.as_ref().map(|mask| {
+            let mut dst = Mat::default().unwrap();
+            mask.copy_to(&mut dst).unwrap();
+            for scale in steps.iter().map(|s| s.get_scale()).filter(|s| *s != 1.0) {
+                let mut scaled = Mat::default().unwrap();
+                resize(
+                    &dst,
+                    &mut scaled,
+                    Size::default(),
+                    scale,
+                    scale,
+                    INTER_NEAREST,
+                )
+                .unwrap();
+                dst = scaled;
+            }
+            dst
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let mut preprocessor = Preprocessor::new();
+        preprocessor.push_step(Resize::new(0.5).unwrap());
+
+        let p = Matrix3x4::new(
+            718.856, 0.0, 607.1928, 0.0, 0.0, 718.856, 185.2157, 0.0, 0.0, 0.0, 1.0, 0.0,
+        );
+        let scaled = preprocessor.scale_camera_params(&[p])[0];
+        assert!((scaled[(0, 0)] - 359.428).abs() < 1e-9);
+        assert!((scaled[(0, 2)] - 303.3464).abs() < 1e-9);
+        assert!((scaled[(1, 2)] - 92.35785).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_mask() {
+        let mut preprocessor = Preprocessor::new();
+        preprocessor.set_mask(Some(
+            Mat::new_rows_cols_with_default(1001, 1001, CV_8U, opencv::core::Scalar::all(255.0))
+                .unwrap(),
+        ));
+        // 逐步取整后为 601 -> 361，整体缩放则为 360
+        preprocessor.push_step(Resize::new(0.6).unwrap());
+        preprocessor.push_step(Resize::new(0.6).unwrap());
+
+        let src =
+            Mat::new_rows_cols_with_default(1001, 1001, CV_8U, opencv::core::Scalar::all(0.0))
+                .unwrap();
+        let dst = preprocessor.process(&src).unwrap();
+        let mask = preprocessor.get_mask().unwrap();
+        assert_eq!(dst.size().unwrap(), Size::new(361, 361));
+        assert_eq!(mask.size().unwrap(), dst.size().unwrap());
+
+        assert!(Resize::new(0.0).is_err());
+        assert!(Resize::new(-0.5).is_err());
+    }
+}
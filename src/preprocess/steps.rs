@@ -0,0 +1,101 @@
+use super::*;
+
+pub struct HistogramEqualization;
+
+pub struct Clahe {
+    clahe: Ptr<dyn CLAHE>,
+}
+
+pub struct Gamma {
+    lut: Mat,
+}
+
+pub struct Resize {
+    scale: f64,
+}
+
+impl HistogramEqualization {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl PreprocessStep for HistogramEqualization {
+    fn apply(&mut self, src: &Mat) -> Result<Mat> {
+        let mut dst = Mat::default().unwrap();
+        equalize_hist(src, &mut dst)
+            .map(|_| dst)
+            .map_err(|_| Error::from(ErrorKind::InvalidData))
+    }
+}
+
+impl Clahe {
+    pub fn new(clip_limit: f64, tile_grid_size: Size) -> Self {
+        Self {
+            clahe: create_clahe(clip_limit, tile_grid_size).unwrap(),
+        }
+    }
+}
+
+impl PreprocessStep for Clahe {
+    fn apply(&mut self, src: &Mat) -> Result<Mat> {
+        let mut dst = Mat::default().unwrap();
+        self.clahe
+            .apply(src, &mut dst)
+            .map(|_| dst)
+            .map_err(|_| Error::from(ErrorKind::InvalidData))
+    }
+}
+
+impl Gamma {
+    pub fn new(gamma: f64) -> Self {
+        let mut lut =
+            Mat::new_rows_cols_with_default(1, 256, CV_8U, opencv::core::Scalar::all(0.0)).unwrap();
+        for i in 0..256 {
+            *lut.at_mut::<u8>(i).unwrap() =
+                (255.0 * (i as f64 / 255.0).powf(gamma)).round().min(255.0) as u8;
+        }
+
+        Self { lut }
+    }
+}
+
+impl PreprocessStep for Gamma {
+    fn apply(&mut self, src: &Mat) -> Result<Mat> {
+        let mut dst = Mat::default().unwrap();
+        opencv::core::lut(src, &self.lut, &mut dst)
+            .map(|_| dst)
+            .map_err(|_| Error::from(ErrorKind::InvalidData))
+    }
+}
+
+impl Resize {
+    // 缩放比例须为正数
+    pub fn new(scale: f64) -> Result<Self> {
+        if !(scale > 0.0 && scale.is_finite()) {
+            return Err(Error::from(ErrorKind::InvalidInput));
+        }
+
+        Ok(Self { scale })
+    }
+}
+
+impl PreprocessStep for Resize {
+    fn apply(&mut self, src: &Mat) -> Result<Mat> {
+        let mut dst = Mat::default().unwrap();
+        resize(
+            src,
+            &mut dst,
+            Size::default(),
+            self.scale,
+            self.scale,
+            INTER_AREA,
+        )
+        .map(|_| dst)
+        .map_err(|_| Error::from(ErrorKind::InvalidData))
+    }
+
+    fn get_scale(&self) -> f64 {
+        self.scale
+    }
+}
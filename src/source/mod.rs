@@ -7,8 +7,10 @@ use opencv::core::*;
 use crate::*;
 
 mod kitti;
+mod rectify;
 
 pub use kitti::*;
+pub use rectify::*;
 
 #[async_trait]
 pub trait CameraSource {
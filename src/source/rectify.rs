@@ -0,0 +1,177 @@
+use opencv::{calib3d::*, imgproc::*};
+
+use super::*;
+use crate::utils::*;
+
+pub struct CameraModel {
+    pub camera_matrix: Matrix3<f64>,
+    // k1 k2 p1 p2 [k3 [k4 k5 k6]]
+    pub distortion: Vec<f64>,
+    pub image_size: Size,
+}
+
+pub struct StereoRectifier {
+    maps: Vec<(Mat, Mat)>,
+    projections: Vec<Matrix3x4<f64>>,
+    baseline: f64,
+}
+
+pub struct RectifiedCameraSource<S: CameraSource> {
+    source: S,
+    rectifier: StereoRectifier,
+}
+
+impl StereoRectifier {
+    // extrinsics 为左相机到右相机的变换，alpha 同 stereoRectify
+    pub fn new(
+        left: &CameraModel,
+        right: &CameraModel,
+        extrinsics: &RnT,
+        alpha: f64,
+    ) -> Result<Self> {
+        let left_cam_mat = matrix_to_mat(&left.camera_matrix);
+        let right_cam_mat = matrix_to_mat(&right.camera_matrix);
+        let left_dist = opencv::core::Vector::<f64>::from_iter(left.distortion.iter().cloned());
+        let right_dist = opencv::core::Vector::<f64>::from_iter(right.distortion.iter().cloned());
+        let r = matrix_to_mat(
+            &UnitQuaternion::from_quaternion(extrinsics.orientation_diff)
+                .to_rotation_matrix()
+                .into_inner(),
+        );
+        let t = matrix_to_mat(&extrinsics.position_diff);
+
+        let mut r_0 = Mat::default().unwrap();
+        let mut r_1 = Mat::default().unwrap();
+        let mut p_0 = Mat::default().unwrap();
+        let mut p_1 = Mat::default().unwrap();
+        let mut q = Mat::default().unwrap();
+        stereo_rectify(
+            &left_cam_mat,
+            &left_dist,
+            &right_cam_mat,
+            &right_dist,
+            left.image_size,
+            &r,
+            &t,
+            &mut r_0,
+            &mut r_1,
+            &mut p_0,
+            &mut p_1,
+            &mut q,
+            CALIB_ZERO_DISPARITY,
+            alpha,
+            left.image_size,
+            &mut Rect::default(),
+            &mut Rect::default(),
+        )
+        .map_err(|_| Error::from(ErrorKind::InvalidInput))?;
+
+        let init_map =
+            |cam_mat: &Mat, dist: &opencv::core::Vector<f64>, r: &Mat, p: &Mat, size: Size| {
+                let mut map_0 = Mat::default().unwrap();
+                let mut map_1 = Mat::default().unwrap();
+                init_undistort_rectify_map(
+                    cam_mat, dist, r, p, size, CV_16SC2, &mut map_0, &mut map_1,
+                )
+                .map(|_| (map_0, map_1))
+                .map_err(|_| Error::from(ErrorKind::InvalidInput))
+            };
+        let maps = vec![
+            init_map(&left_cam_mat, &left_dist, &r_0, &p_0, left.image_size)?,
+            init_map(&right_cam_mat, &right_dist, &r_1, &p_1, left.image_size)?,
+        ];
+
+        Ok(Self {
+            maps,
+            projections: vec![mat_to_matrix3x4(&p_0), mat_to_matrix3x4(&p_1)],
+            baseline: extrinsics.position_diff.norm(),
+        })
+    }
+
+    // 校正后左右相机共用的针孔内参
+    pub fn get_camera_matrix(&self) -> Matrix3<f64> {
+        Matrix3::from(self.projections[0].fixed_columns::<U3>(0))
+    }
+
+    // 校正后的投影矩阵，格式与 KITTI calib.txt 一致
+    pub fn get_projections(&self) -> &[Matrix3x4<f64>] {
+        &self.projections
+    }
+
+    pub fn get_baseline(&self) -> f64 {
+        self.baseline
+    }
+
+    pub fn rectify(&self, images: &[Mat]) -> Result<Vec<Mat>> {
+        if images.len() != self.maps.len() {
+            return Err(Error::from(ErrorKind::InvalidInput));
+        }
+
+        images
+            .iter()
+            .zip(self.maps.iter())
+            .map(|(img, (map_0, map_1))| {
+                let mut dst = Mat::default().unwrap();
+                remap(
+                    img,
+                    &mut dst,
+                    map_0,
+                    map_1,
+                    INTER_LINEAR,
+                    BORDER_CONSTANT,
+                    opencv::core::Scalar::default(),
+                )
+                .map(|_| dst)
+                .map_err(|_| Error::from(ErrorKind::InvalidData))
+            })
+            .collect()
+    }
+}
+
+impl<S: CameraSource> RectifiedCameraSource<S> {
+    pub fn new(source: S, rectifier: StereoRectifier) -> Self {
+        Self { source, rectifier }
+    }
+
+    pub fn get_rectifier(&self) -> &StereoRectifier {
+        &self.rectifier
+    }
+}
+
+#[async_trait]
+impl<S: CameraSource + Send> CameraSource for RectifiedCameraSource<S> {
+    async fn read_camera_params(&mut self) -> Result<Vec<Matrix3x4<f64>>> {
+        Ok(self.rectifier.get_projections().to_vec())
+    }
+
+    async fn read_next(&mut self) -> Result<(SystemTime, Vec<Mat>)> {
+        let (time, images) = self.source.read_next().await?;
+        self.rectifier.rectify(&images).map(|images| (time, images))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let camera = || CameraModel {
+            camera_matrix: Matrix3::new(
+                718.856, 0.0, 607.1928, 0.0, 718.856, 185.2157, 0.0, 0.0, 1.0,
+            ),
+            distortion: vec![0.0; 5],
+            image_size: Size::new(1241, 376),
+        };
+        let extrinsics = RnT {
+            position_diff: Vector3::new(-0.54, 0.0, 0.0),
+            orientation_diff: *UnitQuaternion::identity().quaternion(),
+        };
+
+        let rectifier = StereoRectifier::new(&camera(), &camera(), &extrinsics, 0.0).unwrap();
+        assert!((rectifier.get_baseline() - 0.54).abs() < 1e-9);
+
+        let p = rectifier.get_projections();
+        assert!((p[1][(0, 3)] / p[1][(0, 0)] + 0.54).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,187 @@
+use std::cmp::Ordering;
+use std::time::Duration;
+
+use super::*;
+use crate::utils::ConfigFile;
+
+// 满足任一条件时当前帧成为关键帧
+#[derive(Copy, Clone)]
+pub struct KeyframeConfig {
+    // 与上一关键帧共视点的像素视差中值
+    pub min_parallax: f64,
+    // 上一关键帧的特征点仍被跟踪的比例低于该值
+    pub min_tracked_ratio: f64,
+    pub max_interval: Duration,
+    // 自上一关键帧以来的旋转角，弧度
+    pub max_rotation: f64,
+}
+
+pub struct KeyframeSelector {
+    config: KeyframeConfig,
+}
+
+impl KeyframeConfig {
+    pub fn from_config_file(config: &ConfigFile) -> Result<Self> {
+        let default = Self::default();
+
+        Ok(Self {
+            min_parallax: config.get_or("keyframe.min_parallax", default.min_parallax)?,
+            min_tracked_ratio: config
+                .get_or("keyframe.min_tracked_ratio", default.min_tracked_ratio)?,
+            max_interval: Duration::from_millis(config.get_or(
+                "keyframe.max_interval_ms",
+                default.max_interval.as_millis() as u64,
+            )?),
+            max_rotation: config.get_or("keyframe.max_rotation", default.max_rotation)?,
+        })
+    }
+}
+
+impl Default for KeyframeConfig {
+    fn default() -> Self {
+        Self {
+            min_parallax: 20.0,
+            min_tracked_ratio: 0.5,
+            max_interval: Duration::from_millis(500),
+            max_rotation: 0.1,
+        }
+    }
+}
+
+impl KeyframeSelector {
+    pub fn new() -> Self {
+        Self::with_config(KeyframeConfig::default())
+    }
+
+    pub fn with_config(config: KeyframeConfig) -> Self {
+        Self { config }
+    }
+
+    // tracked 的第 0 帧为候选帧，rotation 为自上一关键帧以来的旋转角
+    pub fn is_keyframe(&self, tracked: &Tracked, rotation: f64) -> bool {
+        let (reference_id, reference_time) = match (
+            tracked.get_last_keyframe_id(),
+            tracked.get_last_keyframe_timestamp(),
+        ) {
+            (Some(reference_id), Some(reference_time)) => (reference_id, reference_time),
+            _ => return true,
+        };
+
+        if rotation >= self.config.max_rotation {
+            return true;
+        }
+
+        if let Some(time) = tracked.get_timestamp(0) {
+            if let Ok(elapsed) = time.duration_since(reference_time) {
+                if elapsed >= self.config.max_interval {
+                    return true;
+                }
+            }
+        }
+
+        // 按轨迹查找关键帧上的观测，关键帧超出视图时仍可比较
+        let frame_id = match tracked.get_frame_id(0) {
+            Some(frame_id) => frame_id,
+            None => return true,
+        };
+        let mut parallaxes = (0..tracked.points_count())
+            .filter_map(|i| {
+                let track = tracked.get_track(i)?;
+                let p = track.get_observation(frame_id)?.point;
+                let p_ref = track.get_observation(reference_id)?.point;
+                Some((p.vp_position - p_ref.vp_position).norm())
+            })
+            .collect::<Vec<f64>>();
+        if parallaxes.is_empty() {
+            return true;
+        }
+
+        let reference_count = tracked.get_last_keyframe_points_count();
+        if (parallaxes.len() as f64) < self.config.min_tracked_ratio * reference_count as f64 {
+            return true;
+        }
+
+        parallaxes.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+        parallaxes[parallaxes.len() / 2] >= self.config.min_parallax
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::SystemTime;
+
+    use super::super::test::matched_shifted as matched;
+    use super::*;
+
+    #[test]
+    fn test() {
+        let mut tracker = Tracker::new(16);
+        tracker.set_keyframe_selector(Some(KeyframeSelector::new()));
+        let time = SystemTime::now();
+
+        tracker.update_matched(&time, &matched(10, 0.0, false));
+        assert!(tracker.get_tracked().is_keyframe(0));
+
+        // 视差不足
+        tracker.update_matched(&time, &matched(10, 5.0, true));
+        let tracked = tracker.get_tracked();
+        assert!(!tracked.is_keyframe(0));
+        assert_eq!(tracked.get_last_keyframe(), Some(1));
+
+        // 相对上一关键帧的视差累积到阈值
+        tracker.update_matched(&time, &matched(10, 25.0, true));
+        assert!(tracker.get_tracked().is_keyframe(0));
+
+        // 旋转过大
+        tracker.set_motion(&RnT {
+            position_diff: Vector3::zeros(),
+            orientation_diff: *UnitQuaternion::from_euler_angles(0.0, 0.2, 0.0).quaternion(),
+        });
+        tracker.update_matched(&time, &matched(10, 25.0, true));
+        assert!(tracker.get_tracked().is_keyframe(0));
+
+        // 跟踪比例过低
+        tracker.update_matched(&time, &matched(3, 25.0, true));
+        assert!(tracker.get_tracked().is_keyframe(0));
+
+        // 间隔过长
+        tracker.update_matched(&(time + Duration::from_secs(1)), &matched(3, 25.0, true));
+        assert!(tracker.get_tracked().is_keyframe(0));
+    }
+
+    #[test]
+    fn test_reference() {
+        let mut tracker = Tracker::new(3);
+        tracker.set_max_observations(16);
+        tracker.set_keyframe_selector(Some(KeyframeSelector::new()));
+        let time = SystemTime::now();
+
+        tracker.update_matched(&time, &matched(10, 0.0, false));
+        for _ in 0..4 {
+            tracker.update_matched(&time, &matched(10, 5.0, true));
+        }
+        // 关键帧已超出缓存帧，仍以它为参考计算视差
+        let tracked = tracker.get_tracked();
+        assert!(!tracked.is_keyframe(0));
+        assert_eq!(tracked.get_last_keyframe(), None);
+        assert_eq!(tracked.get_last_keyframe_id(), Some(0));
+
+        tracker.update_matched(&time, &matched(10, 25.0, true));
+        let tracked = tracker.get_tracked();
+        assert!(tracked.is_keyframe(0));
+        assert_eq!(tracked.get_last_keyframe_id(), Some(0));
+
+        // 与上一关键帧没有共视点
+        let mut tracker = Tracker::new(16);
+        tracker.set_keyframe_selector(Some(KeyframeSelector::with_config(KeyframeConfig {
+            min_tracked_ratio: 0.0,
+            ..KeyframeConfig::default()
+        })));
+        tracker.update_matched(&time, &matched(10, 0.0, false));
+        tracker.update_matched(&time, &matched(10, 0.0, false));
+        let tracked = tracker.get_tracked();
+        assert!(tracked.is_keyframe(0));
+        assert_eq!(tracked.get_last_keyframe(), None);
+        assert_eq!(tracked.get_last_keyframe_id(), Some(0));
+    }
+}
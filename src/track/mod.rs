@@ -1,171 +1,692 @@
-use std::collections::LinkedList;
+use std::collections::{HashMap, VecDeque};
 use std::time::SystemTime;
 
 use nalgebra::*;
 
 use crate::*;
 
+mod keyframe;
+
+pub use keyframe::*;
+
+pub type TrackEndedCallback = Box<dyn FnMut(&Track) + Send>;
+
 pub struct Tracker {
     max_frames_buffered: u32,
-    frames: LinkedList<Frame>,
+    // 环形缓冲，最新一帧在末尾
+    frames: VecDeque<Frame>,
+    tracks: HashMap<u64, Track>,
+    // 已结束的轨迹，按结束先后保留最多 max_history 条
+    history: HashMap<u64, Track>,
+    history_order: VecDeque<u64>,
+    max_history: usize,
+    // 应与 MatcherConfig::max_frame_gap 一致，超过该帧数未被延续的轨迹视为结束
+    max_frame_gap: u32,
+    // 仍在延续的轨迹保留的最近观测数，默认不限制
+    max_observations: usize,
+    on_track_ended: Option<TrackEndedCallback>,
+    // 为 None 时不标记关键帧
+    keyframe_selector: Option<KeyframeSelector>,
+    // 最新一帧之前最近的关键帧，不受缓存帧数与视图范围限制
+    last_keyframe: Option<Frame>,
+    // 自上一关键帧以来累积的旋转
+    rotation: UnitQuaternion<f64>,
+    next_frame_id: u64,
+    next_track_id: u64,
 }
 
+#[derive(Clone)]
 struct Frame {
+    id: u64,
     timestamp: SystemTime,
-    points: Vec<Point>,
+    keyframe: bool,
+    // 各特征点所属的轨迹
+    track_ids: Vec<u64>,
 }
 
-struct Point {
-    prev_index: u32,
-    vp_position: Vector2<f64>,
-    match_degree: f64,
+// 借用 Tracker 的只读视图，第 0 帧为最新一帧，点序号为其在最新一帧中的序号
+pub struct Tracked<'a> {
+    tracker: &'a Tracker,
+    frames_count: u32,
 }
 
-pub struct Tracked {
-    frames: Vec<TrackedFrame>,
+#[derive(Copy, Clone)]
+pub struct TrackedPoint {
+    pub vp_position: Vector2<f64>,
+    pub depth: Option<f64>,
 }
 
-struct TrackedFrame {
-    timestamp: SystemTime,
-    points: Vec<TrackedPoint>,
+// 同一特征点在各帧中的观测，ID 在创建时分配且不再改变
+pub struct Track {
+    id: u64,
+    // 创建时的帧号与累计观测次数，不随观测被移除而改变
+    first_frame_id: u64,
+    observations_count: usize,
+    observations: VecDeque<Observation>,
+    // 最近一次观测的二进制描述子
+    descriptor: Option<Vec<u8>>,
+    ended: bool,
 }
 
 #[derive(Copy, Clone)]
-pub struct TrackedPoint {
-    pub vp_position: Vector2<f64>,
+pub struct Observation {
+    pub frame_id: u64,
+    pub point_index: u32,
+    pub point: TrackedPoint,
 }
 
 impl Tracker {
     pub fn new(max_frames_buffered: u32) -> Self {
+        Self::with_matcher_config(max_frames_buffered, &feature::MatcherConfig::default())
+    }
+
+    // 轨迹结束的判断与匹配器允许的最大间隔保持一致
+    pub fn with_matcher_config(
+        max_frames_buffered: u32,
+        matcher_config: &feature::MatcherConfig,
+    ) -> Self {
         Self {
             max_frames_buffered,
-            frames: LinkedList::new(),
+            frames: VecDeque::with_capacity(max_frames_buffered as usize),
+            tracks: HashMap::new(),
+            history: HashMap::new(),
+            history_order: VecDeque::new(),
+            max_history: 1024,
+            max_frame_gap: matcher_config.max_frame_gap,
+            max_observations: usize::MAX,
+            on_track_ended: None,
+            keyframe_selector: None,
+            last_keyframe: None,
+            rotation: UnitQuaternion::identity(),
+            next_frame_id: 0,
+            next_track_id: 0,
         }
     }
 
+    // 应与匹配器的 max_frame_gap 一致
+    pub fn set_max_frame_gap(&mut self, max_frame_gap: u32) {
+        self.max_frame_gap = max_frame_gap;
+    }
+
+    // 只约束仍在延续的轨迹，轨迹结束后不再裁剪，历史的内存只受 max_history 约束
+    // 只作用于之后加入的观测，len() 仍为累计观测次数
+    pub fn set_max_observations(&mut self, max_observations: usize) {
+        self.max_observations = max_observations.max(1);
+    }
+
+    pub fn set_max_history(&mut self, max_history: usize) {
+        self.max_history = max_history;
+        self.trim_history();
+    }
+
+    // 轨迹结束时回调，每条轨迹只回调一次，结束后不再被延续
+    pub fn set_track_ended_callback(&mut self, callback: Option<TrackEndedCallback>) {
+        self.on_track_ended = callback;
+    }
+
+    pub fn set_keyframe_selector(&mut self, selector: Option<KeyframeSelector>) {
+        self.keyframe_selector = selector;
+    }
+
+    // 上一帧到下一帧的运动，供关键帧判断旋转量
+    pub fn set_motion(&mut self, motion: &RnT) {
+        self.rotation = UnitQuaternion::from_quaternion(motion.orientation_diff) * self.rotation;
+    }
+
     pub fn update_matched(
         &mut self,
         timestamp: &SystemTime,
         matched_features: &[feature::MatchedFeature],
     ) {
-        let points = matched_features
-            .iter()
-            .map(|mp| Point {
-                prev_index: mp.prev_index,
-                vp_position: mp.position,
-                match_degree: mp.match_degree,
-            })
-            .collect();
-
-        let frame = Frame {
-            timestamp: *timestamp,
-            points,
-        };
+        let frame_id = self.next_frame_id;
+        self.next_frame_id += 1;
 
-        if self.frames.len() == self.max_frames_buffered as usize {
-            self.frames.pop_back();
+        if let Some(frame) = self.frames.back().filter(|frame| frame.keyframe) {
+            self.last_keyframe = Some(frame.clone());
         }
-        self.frames.push_front(frame);
-    }
 
-    pub fn get_tracked(&self) -> Tracked {
-        let get_index = |p: &Point| {
-            if p.match_degree > 0.0 {
-                p.prev_index
+        let mut track_ids = Vec::with_capacity(matched_features.len());
+        for (i, mp) in matched_features.iter().enumerate() {
+            // 延续上一次观测所在的轨迹，否则新建轨迹
+            let prev_track_id = if mp.match_degree > 0.0 && mp.prev_frame_offset > 0 {
+                self.frames
+                    .len()
+                    .checked_sub(mp.prev_frame_offset as usize)
+                    .and_then(|index| self.frames.get(index))
+                    .and_then(|frame| frame.track_ids.get(mp.prev_index as usize))
+                    .copied()
+                    // 已结束或被移除的轨迹不再延续，按新轨迹处理
+                    .filter(|track_id| self.tracks.contains_key(track_id))
             } else {
-                u32::MAX
+                None
+            };
+            let track_id = match prev_track_id {
+                Some(track_id) => track_id,
+                None => {
+                    self.next_track_id += 1;
+                    self.next_track_id - 1
+                }
+            };
+
+            let observation = Observation {
+                frame_id,
+                point_index: i as u32,
+                point: TrackedPoint {
+                    vp_position: mp.position,
+                    depth: mp.depth,
+                },
+            };
+            let track = self.tracks.entry(track_id).or_insert_with(|| Track {
+                id: track_id,
+                first_frame_id: frame_id,
+                observations_count: 0,
+                observations: VecDeque::new(),
+                descriptor: None,
+                ended: false,
+            });
+            track.observations.push_back(observation);
+            track.observations_count += 1;
+            while track.observations.len() > self.max_observations {
+                track.observations.pop_front();
+            }
+            if mp.descriptor.is_some() {
+                track.descriptor = mp.descriptor.clone();
+            }
+
+            track_ids.push(track_id);
+        }
+
+        self.end_tracks(frame_id);
+
+        if self.frames.len() == self.max_frames_buffered as usize {
+            if let Some(frame) = self.frames.pop_front() {
+                self.evict(&frame);
             }
+        }
+        self.frames.push_back(Frame {
+            id: frame_id,
+            timestamp: *timestamp,
+            keyframe: false,
+            track_ids,
+        });
+
+        if let Some(selector) = self.keyframe_selector.as_ref() {
+            if selector.is_keyframe(&self.get_tracked(), self.rotation.angle()) {
+                self.frames.back_mut().unwrap().keyframe = true;
+                self.rotation = UnitQuaternion::identity();
+            }
+        }
+    }
+
+    // 刚超出可匹配范围的那一帧上未被延续的轨迹即为结束
+    fn end_tracks(&mut self, frame_id: u64) {
+        let gap = self.max_frame_gap as usize + 1;
+        let frames = &self.frames;
+        let (last_frame_id, track_ids) = match frames
+            .len()
+            .checked_sub(gap)
+            .and_then(|index| frames.get(index))
+        {
+            Some(frame) if frame.id + gap as u64 == frame_id => (frame.id, frame.track_ids.clone()),
+            _ => return,
         };
 
-        let mut prev_indexes = Vec::new();
-        let mut tracked = Tracked { frames: Vec::new() };
-        let mut first_frame = true;
-        'a: for matched_frame in &self.frames {
-            let tracked_points = if first_frame {
-                first_frame = false;
-
-                prev_indexes = matched_frame.points.iter().map(|p| get_index(p)).collect();
-
-                matched_frame
-                    .points
-                    .iter()
-                    .map(|p| TrackedPoint {
-                        vp_position: p.vp_position,
-                    })
-                    .collect()
-            } else {
-                let mut tracked_points = vec![TrackedPoint::default(); prev_indexes.len()];
-                for (prev_index, tp) in prev_indexes.iter_mut().zip(tracked_points.iter_mut()) {
-                    if let Some(p) = matched_frame.points.get(*prev_index as usize) {
-                        *prev_index = get_index(p);
-                        tp.vp_position = p.vp_position;
-                    }
-                }
+        self.end_tracks_of(&track_ids, last_frame_id);
+    }
 
-                tracked_points
-            };
+    // 将最后一次观测在 last_frame_id 的轨迹移入历史
+    fn end_tracks_of(&mut self, track_ids: &[u64], last_frame_id: u64) {
+        for track_id in track_ids {
+            let ended = self
+                .tracks
+                .get(track_id)
+                .map(|track| track.get_last_frame() <= last_frame_id)
+                .unwrap_or(false);
+            if !ended {
+                continue;
+            }
 
-            tracked.frames.push(TrackedFrame {
-                timestamp: matched_frame.timestamp,
-                points: tracked_points,
-            });
+            let mut track = self.tracks.remove(track_id).unwrap();
+            track.ended = true;
+            if let Some(callback) = self.on_track_ended.as_mut() {
+                callback(&track);
+            }
+            self.history.insert(*track_id, track);
+            self.history_order.push_back(*track_id);
+        }
+
+        self.trim_history();
+    }
 
-            if !prev_indexes.iter().fold(
-                false,
-                |flag, index| if *index != u32::MAX { true } else { flag },
-            ) {
-                break 'a;
+    fn trim_history(&mut self) {
+        while self.history_order.len() > self.max_history {
+            if let Some(track_id) = self.history_order.pop_front() {
+                self.history.remove(&track_id);
             }
         }
+    }
+
+    // 被丢弃的帧无法再被匹配，其上仍未延续的轨迹随之结束
+    fn evict(&mut self, frame: &Frame) {
+        self.end_tracks_of(&frame.track_ids, frame.id);
+    }
 
-        tracked
+    // 丢弃轨迹及其全部观测，不触发结束回调，用于剔除外点
+    pub fn remove_tracks(&mut self, track_ids: &[u64]) {
+        for track_id in track_ids {
+            self.tracks.remove(track_id);
+            if self.history.remove(track_id).is_some() {
+                self.history_order.retain(|id| id != track_id);
+            }
+        }
+    }
+
+    // 先查找仍在延续的轨迹，再查找已结束的轨迹
+    pub fn get_track(&self, track_id: u64) -> Option<&Track> {
+        self.tracks
+            .get(&track_id)
+            .or_else(|| self.history.get(&track_id))
+    }
+
+    pub fn tracks(&self) -> impl Iterator<Item = &Track> {
+        self.tracks.values()
+    }
+
+    // 按结束先后，从旧到新
+    pub fn ended_tracks(&self) -> impl Iterator<Item = &Track> {
+        self.history_order
+            .iter()
+            .filter_map(move |track_id| self.history.get(track_id))
+    }
+
+    // 最新一帧的帧号，帧号从 0 开始递增
+    pub fn get_frame_id(&self) -> Option<u64> {
+        self.frames.back().map(|frame| frame.id)
+    }
+
+    pub fn get_tracked(&self) -> Tracked<'_> {
+        // 只保留最新一帧的轨迹能回溯到的帧
+        let frames_count = match self.frames.back() {
+            Some(frame) => frame
+                .track_ids
+                .iter()
+                .filter_map(|track_id| self.tracks.get(track_id))
+                .map(|track| frame.id - track.get_first_frame() + 1)
+                .max()
+                .unwrap_or(1)
+                .min(self.frames.len() as u64) as u32,
+            None => 0,
+        };
+
+        Tracked {
+            tracker: self,
+            frames_count,
+        }
     }
 }
 
-impl Tracked {
+impl<'a> Tracked<'a> {
     pub fn frames_count(&self) -> u32 {
-        self.frames.len() as u32
+        self.frames_count
     }
 
     pub fn points_count(&self) -> u32 {
-        if let Some(frame) = self.frames.get(0) {
-            frame.points.len() as u32
-        } else {
-            0
-        }
+        self.tracker
+            .frames
+            .back()
+            .map(|frame| frame.track_ids.len() as u32)
+            .unwrap_or(0)
     }
 
-    pub fn get_timestamp(&self, frame_index: u32) -> Option<SystemTime> {
-        if let Some(frame) = self.frames.get(frame_index as usize) {
-            Some(frame.timestamp)
+    fn get_frame(&self, frame_index: u32) -> Option<&'a Frame> {
+        if frame_index < self.frames_count {
+            let frames = &self.tracker.frames;
+            frames.get(frames.len() - 1 - frame_index as usize)
         } else {
             None
         }
     }
 
-    pub fn get_point(&self, frame_index: u32, point_index: u32) -> Option<TrackedPoint> {
-        if let Some(frame) = self.frames.get(frame_index as usize) {
-            if let Some(point) = frame.points.get(point_index as usize) {
-                if !point.vp_position.x.is_nan() {
-                    return Some(*point);
-                }
-            }
-        }
+    pub fn frame_points_count(&self, frame_index: u32) -> u32 {
+        self.get_frame(frame_index)
+            .map(|frame| frame.track_ids.len() as u32)
+            .unwrap_or(0)
+    }
+
+    pub fn is_keyframe(&self, frame_index: u32) -> bool {
+        self.get_frame(frame_index)
+            .map(|frame| frame.keyframe)
+            .unwrap_or(false)
+    }
+
+    // 第 0 帧之前最近的关键帧在视图中的序号，超出视图时为 None
+    pub fn get_last_keyframe(&self) -> Option<u32> {
+        let frame_id = self.get_frame_id(0)?;
+        self.get_last_keyframe_id()
+            .map(|keyframe_id| (frame_id - keyframe_id) as u32)
+            .filter(|frame_index| *frame_index < self.frames_count)
+    }
 
-        None
+    // 第 0 帧之前最近的关键帧的帧号，可能已超出视图
+    pub fn get_last_keyframe_id(&self) -> Option<u64> {
+        self.tracker.last_keyframe.as_ref().map(|frame| frame.id)
+    }
+
+    pub fn get_last_keyframe_timestamp(&self) -> Option<SystemTime> {
+        self.tracker
+            .last_keyframe
+            .as_ref()
+            .map(|frame| frame.timestamp)
+    }
+
+    pub fn get_last_keyframe_points_count(&self) -> u32 {
+        self.tracker
+            .last_keyframe
+            .as_ref()
+            .map(|frame| frame.track_ids.len() as u32)
+            .unwrap_or(0)
+    }
+
+    pub fn get_frame_id(&self, frame_index: u32) -> Option<u64> {
+        self.get_frame(frame_index).map(|frame| frame.id)
+    }
+
+    pub fn get_timestamp(&self, frame_index: u32) -> Option<SystemTime> {
+        self.get_frame(frame_index).map(|frame| frame.timestamp)
+    }
+
+    pub fn get_track_id(&self, point_index: u32) -> Option<u64> {
+        self.tracker
+            .frames
+            .back()
+            .and_then(|frame| frame.track_ids.get(point_index as usize))
+            .copied()
+    }
+
+    pub fn get_track(&self, point_index: u32) -> Option<&'a Track> {
+        self.get_track_id(point_index)
+            .and_then(|track_id| self.tracker.tracks.get(&track_id))
+    }
+
+    pub fn get_point(&self, frame_index: u32, point_index: u32) -> Option<TrackedPoint> {
+        let frame = self.get_frame(frame_index)?;
+        self.get_track(point_index)?
+            .get_observation(frame.id)
+            .map(|o| o.point)
     }
 }
 
-impl TrackedPoint {
-    fn default() -> Self {
-        Self {
-            vp_position: Vector2::<f64>::new(f64::NAN, f64::NAN),
-        }
+impl Track {
+    pub fn get_id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn get_first_frame(&self) -> u64 {
+        self.first_frame_id
+    }
+
+    pub fn get_last_frame(&self) -> u64 {
+        self.observations.back().unwrap().frame_id
+    }
+
+    // 创建以来的观测次数，包括已不在 get_observations 中的观测
+    pub fn len(&self) -> usize {
+        self.observations_count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.observations_count == 0
+    }
+
+    pub fn is_ended(&self) -> bool {
+        self.ended
+    }
+
+    pub fn get_descriptor(&self) -> Option<&[u8]> {
+        self.descriptor.as_deref()
+    }
+
+    // 按帧号从旧到新
+    pub fn get_observations(&self) -> impl Iterator<Item = &Observation> {
+        self.observations.iter()
+    }
+
+    pub fn get_observation(&self, frame_id: u64) -> Option<&Observation> {
+        self.observations
+            .binary_search_by_key(&frame_id, |o| o.frame_id)
+            .ok()
+            .map(|i| &self.observations[i])
     }
 }
 
 #[cfg(test)]
 mod test {
+    use super::*;
+
+    fn matched(prev: &[(u32, u32)]) -> Vec<feature::MatchedFeature> {
+        matched_with(prev, 1.0, 0.0)
+    }
+
+    // count 个间隔 10 像素的点整体平移 shift，continued 时逐一延续上一帧的同序号点
+    pub(super) fn matched_shifted(
+        count: usize,
+        shift: f64,
+        continued: bool,
+    ) -> Vec<feature::MatchedFeature> {
+        let prev = (0..count as u32)
+            .map(|i| (if continued { i } else { u32::MAX }, 1))
+            .collect::<Vec<(u32, u32)>>();
+        matched_with(&prev, 10.0, shift)
+    }
+
+    // 第 i 个点位于 (i·spacing + shift, 0)，prev_index 为 u32::MAX 时为新点
+    fn matched_with(prev: &[(u32, u32)], spacing: f64, shift: f64) -> Vec<feature::MatchedFeature> {
+        prev.iter()
+            .enumerate()
+            .map(
+                |(i, (prev_index, prev_frame_offset))| feature::MatchedFeature {
+                    prev_index: *prev_index,
+                    prev_frame_offset: *prev_frame_offset,
+                    position: Vector2::new(i as f64 * spacing + shift, 0.0),
+                    match_degree: if *prev_index != u32::MAX { 1.0 } else { 0.0 },
+                    depth: None,
+                    descriptor: None,
+                },
+            )
+            .collect()
+    }
+
+    // 允许跳过一帧的匹配
+    fn tracker(max_frames_buffered: u32) -> Tracker {
+        let mut tracker = Tracker::new(max_frames_buffered);
+        tracker.set_max_frame_gap(1);
+        tracker
+    }
+
     #[test]
-    fn test() {}
+    fn test() {
+        let mut tracker = tracker(16);
+        let time = SystemTime::now();
+        tracker.update_matched(&time, &matched(&[(u32::MAX, 1), (u32::MAX, 1)]));
+        tracker.update_matched(&time, &matched(&[(0, 1)]));
+        // 第 2 个点跳过一帧，延续最早一帧的第 2 个点
+        tracker.update_matched(&time, &matched(&[(0, 1), (1, 2)]));
+
+        let tracked = tracker.get_tracked();
+        assert_eq!(tracked.frames_count(), 3);
+        assert_eq!(tracked.points_count(), 2);
+        assert_eq!(tracked.get_point(2, 0).unwrap().vp_position.x, 0.0);
+        assert!(tracked.get_point(1, 1).is_none());
+        assert_eq!(tracked.get_point(2, 1).unwrap().vp_position.x, 1.0);
+    }
+
+    #[test]
+    fn test_track_id() {
+        let mut tracker = tracker(3);
+        let time = SystemTime::now();
+        tracker.update_matched(&time, &matched(&[(u32::MAX, 1), (u32::MAX, 1)]));
+        tracker.update_matched(&time, &matched(&[(1, 1), (u32::MAX, 1)]));
+        tracker.update_matched(&time, &matched(&[(0, 1), (0, 2)]));
+
+        let tracked = tracker.get_tracked();
+        let id_0 = tracked.get_track_id(0).unwrap();
+        let id_1 = tracked.get_track_id(1).unwrap();
+        assert_eq!(id_0, 1);
+        assert_eq!(id_1, 0);
+        assert_eq!(tracker.get_frame_id(), Some(2));
+
+        let track = tracker.get_track(id_0).unwrap();
+        assert_eq!(track.len(), 3);
+        assert_eq!(track.get_first_frame(), 0);
+        assert_eq!(track.get_last_frame(), 2);
+        let track = tracker.get_track(id_1).unwrap();
+        assert_eq!(track.len(), 2);
+        assert_eq!(
+            track
+                .get_observations()
+                .map(|o| o.frame_id)
+                .collect::<Vec<u64>>(),
+            vec![0, 2]
+        );
+
+        // 超出缓存帧数后，ID、首帧与观测次数保持创建以来的总计
+        tracker.update_matched(&time, &matched(&[(0, 1)]));
+        assert_eq!(tracker.get_tracked().get_track_id(0), Some(id_0));
+        let track = tracker.get_track(id_0).unwrap();
+        assert_eq!(track.get_first_frame(), 0);
+        assert_eq!(track.len(), 4);
+        // 观测不随缓存帧一起丢弃
+        assert_eq!(track.get_observations().count(), 4);
+        assert_eq!(track.get_observations().next().unwrap().frame_id, 0);
+        assert!(tracker.get_track(id_1).is_some());
+        assert!(!tracker.get_track(id_1).unwrap().is_ended());
+        // 第 1 帧新建的轨迹超出允许的间隔
+        assert!(tracker.get_track(2).unwrap().is_ended());
+        assert_eq!(tracker.tracks().count(), 2);
+    }
+
+    #[test]
+    fn test_ring_buffer() {
+        let mut tracker = Tracker::new(4);
+        let time = SystemTime::now();
+        tracker.update_matched(&time, &matched(&[(u32::MAX, 1)]));
+        for _ in 0..10 {
+            tracker.update_matched(&time, &matched(&[(0, 1), (u32::MAX, 1)]));
+        }
+
+        // 只保留缓存的帧，长轨迹的视图随之截断
+        let tracked = tracker.get_tracked();
+        assert_eq!(tracked.frames_count(), 4);
+        assert_eq!(tracked.points_count(), 2);
+        assert!(tracked.get_point(3, 0).is_some());
+        assert!(tracked.get_point(4, 0).is_none());
+        assert!(tracked.get_point(1, 1).is_none());
+        assert_eq!(tracked.get_track(0).unwrap().len(), 11);
+        assert_eq!(tracked.get_track(0).unwrap().get_observations().count(), 11);
+        assert_eq!(tracker.tracks().count(), 2);
+    }
+
+    #[test]
+    fn test_history() {
+        use std::sync::{Arc, Mutex};
+
+        let ended = Arc::new(Mutex::new(Vec::new()));
+        let mut tracker = tracker(2);
+        tracker.set_track_ended_callback(Some(Box::new({
+            let ended = ended.clone();
+            move |track: &Track| ended.lock().unwrap().push((track.get_id(), track.len()))
+        })));
+
+        let time = SystemTime::now();
+        tracker.update_matched(&time, &matched(&[(u32::MAX, 1), (u32::MAX, 1)]));
+        tracker.update_matched(&time, &matched(&[(0, 1)]));
+        tracker.update_matched(&time, &matched(&[(0, 1)]));
+        // 第 2 条轨迹在允许的间隔内未被延续
+        assert_eq!(*ended.lock().unwrap(), vec![(1, 1)]);
+
+        tracker.update_matched(&time, &matched(&[(u32::MAX, 1)]));
+        tracker.update_matched(&time, &matched(&[(u32::MAX, 1)]));
+        assert_eq!(*ended.lock().unwrap(), vec![(1, 1), (0, 3)]);
+
+        // 已结束的轨迹保留全部观测，不随缓存帧一起丢弃
+        let track = tracker.get_track(0).unwrap();
+        assert!(track.is_ended());
+        assert_eq!(track.len(), 3);
+        assert_eq!(track.get_first_frame(), 0);
+        assert_eq!(track.get_observations().count(), 3);
+        assert_eq!(
+            tracker
+                .ended_tracks()
+                .map(|t| t.get_id())
+                .collect::<Vec<u64>>(),
+            vec![1, 0]
+        );
+
+        tracker.set_max_history(1);
+        assert!(tracker.get_track(1).is_none());
+        assert!(tracker.get_track(0).is_some());
+    }
+
+    #[test]
+    fn test_remove_tracks() {
+        let mut tracker = tracker(2);
+        let time = SystemTime::now();
+        tracker.update_matched(&time, &matched(&[(u32::MAX, 1), (u32::MAX, 1)]));
+        tracker.update_matched(&time, &matched(&[(0, 1)]));
+        tracker.update_matched(&time, &matched(&[(0, 1)]));
+        assert!(tracker.get_track(1).unwrap().is_ended());
+
+        // 移除已结束与仍在延续的轨迹
+        tracker.remove_tracks(&[0, 1]);
+        assert!(tracker.get_track(0).is_none());
+        assert!(tracker.get_track(1).is_none());
+        assert_eq!(tracker.ended_tracks().count(), 0);
+        assert_eq!(tracker.tracks().count(), 0);
+
+        // 被移除的轨迹不再延续，匹配到的点分配新的 ID
+        tracker.update_matched(&time, &matched(&[(0, 1)]));
+        assert_eq!(tracker.get_tracked().get_track_id(0), Some(2));
+        assert_eq!(tracker.get_track(2).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_ended_track() {
+        use std::sync::{Arc, Mutex};
+
+        let ended = Arc::new(Mutex::new(Vec::new()));
+        let mut tracker = Tracker::new(4);
+        tracker.set_max_observations(2);
+        tracker.set_track_ended_callback(Some(Box::new({
+            let ended = ended.clone();
+            move |track: &Track| ended.lock().unwrap().push(track.get_id())
+        })));
+
+        let time = SystemTime::now();
+        tracker.update_matched(&time, &matched(&[(u32::MAX, 1), (u32::MAX, 1)]));
+        tracker.update_matched(&time, &matched(&[(0, 1)]));
+        assert_eq!(*ended.lock().unwrap(), vec![1]);
+
+        // 结束回调之后的匹配不再延续已结束的轨迹，而是分配新的 ID
+        tracker.update_matched(&time, &matched(&[(0, 1), (1, 2)]));
+        let tracked = tracker.get_tracked();
+        assert_eq!(tracked.get_track_id(0), Some(0));
+        assert_eq!(tracked.get_track_id(1), Some(2));
+        let track = tracker.get_track(1).unwrap();
+        assert!(track.is_ended());
+        assert_eq!(track.len(), 1);
+        assert_eq!(*ended.lock().unwrap(), vec![1]);
+
+        // 延续中的轨迹超出观测上限时移除最早的观测
+        tracker.update_matched(&time, &matched(&[(0, 1)]));
+        let track = tracker.get_track(0).unwrap();
+        assert_eq!(track.len(), 4);
+        assert_eq!(track.get_first_frame(), 0);
+        assert_eq!(
+            track
+                .get_observations()
+                .map(|o| o.frame_id)
+                .collect::<Vec<u64>>(),
+            vec![2, 3]
+        );
+    }
 }
@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::*;
+
+// 每行一个 `key = value`，`#` 之后为注释
+pub struct ConfigFile {
+    values: HashMap<String, String>,
+}
+
+impl ConfigFile {
+    pub async fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::parse(&async_std::fs::read_to_string(path.as_ref()).await?)
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut values = HashMap::new();
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if !line.is_empty() {
+                let mut fields = line.splitn(2, '=');
+                match (fields.next(), fields.next()) {
+                    (Some(key), Some(value)) if !key.trim().is_empty() => {
+                        values.insert(key.trim().to_string(), value.trim().to_string());
+                    }
+                    _ => return Err(Error::from(ErrorKind::InvalidData)),
+                }
+            }
+        }
+
+        Ok(Self { values })
+    }
+
+    pub fn get<T: FromStr>(&self, key: &str) -> Result<Option<T>> {
+        match self.values.get(key) {
+            Some(value) => value
+                .parse::<T>()
+                .map(Some)
+                .map_err(|_| Error::from(ErrorKind::InvalidData)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_or<T: FromStr>(&self, key: &str, default: T) -> Result<T> {
+        self.get(key).map(|value| value.unwrap_or(default))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let config = ConfigFile::parse(
+            "# comment\nextractor.features_count = 1000\n\nextractor.scale_factor=1.2 # inline\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.get::<i32>("extractor.features_count").unwrap(),
+            Some(1000)
+        );
+        assert_eq!(
+            config.get_or("extractor.scale_factor", 2.0f32).unwrap(),
+            1.2
+        );
+        assert_eq!(config.get::<i32>("extractor.levels_count").unwrap(), None);
+        assert!(config.get::<i32>("extractor.scale_factor").is_err());
+        assert!(ConfigFile::parse("no_value").is_err());
+    }
+}
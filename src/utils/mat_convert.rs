@@ -0,0 +1,47 @@
+use nalgebra::*;
+use opencv::core::*;
+
+pub fn matrix_to_mat<R: Dim, C: Dim, S: storage::Storage<f64, R, C>>(
+    matrix: &Matrix<f64, R, C, S>,
+) -> Mat {
+    let mut mat = Mat::zeros(matrix.nrows() as i32, matrix.ncols() as i32, CV_64F)
+        .unwrap()
+        .to_mat()
+        .unwrap();
+    for i in 0..matrix.nrows() {
+        for j in 0..matrix.ncols() {
+            *mat.at_2d_mut(i as i32, j as i32).unwrap() = matrix[(i, j)];
+        }
+    }
+
+    mat
+}
+
+pub fn mat_to_matrix3(mat: &Mat) -> Matrix3<f64> {
+    Matrix3::from_fn(|i, j| *mat.at_2d::<f64>(i as i32, j as i32).unwrap())
+}
+
+pub fn mat_to_matrix3x4(mat: &Mat) -> Matrix3x4<f64> {
+    Matrix3x4::from_fn(|i, j| *mat.at_2d::<f64>(i as i32, j as i32).unwrap())
+}
+
+pub fn mat_to_vector3(mat: &Mat) -> Vector3<f64> {
+    Vector3::new(
+        *mat.at(0).unwrap(),
+        *mat.at(1).unwrap(),
+        *mat.at(2).unwrap(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let m = Matrix3x4::new(
+            1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0, 11.0, 12.0,
+        );
+        assert_eq!(mat_to_matrix3x4(&matrix_to_mat(&m)), m);
+    }
+}
@@ -1,3 +1,13 @@
+mod config_file;
+#[cfg(feature = "opencv")]
+mod mat_convert;
+#[cfg(all(test, feature = "opencv"))]
+pub mod test_scene;
+#[cfg(feature = "opencv")]
 mod tracked_viewer;
 
+pub use config_file::*;
+#[cfg(feature = "opencv")]
+pub use mat_convert::*;
+#[cfg(feature = "opencv")]
 pub use tracked_viewer::*;
@@ -0,0 +1,41 @@
+use nalgebra::*;
+
+// 测试共用的针孔相机内参，图像 640x480
+pub fn camera_matrix() -> Matrix3<f64> {
+    Matrix3::new(500.0, 0.0, 320.0, 0.0, 500.0, 240.0, 0.0, 0.0, 1.0)
+}
+
+// 以光轴为中心、每行 cols 个点的网格，点间距 spacing，第 i 个点的深度为 depth(i)
+pub fn grid_landmarks(
+    count: usize,
+    cols: usize,
+    spacing: f64,
+    depth: impl Fn(usize) -> f64,
+) -> Vec<Vector3<f64>> {
+    let rows = (count + cols - 1) / cols;
+    (0..count)
+        .map(|i| {
+            Vector3::new(
+                ((i % cols) as f64 - (cols - 1) as f64 / 2.0) * spacing,
+                ((i / cols) as f64 - (rows - 1) as f64 / 2.0) * spacing,
+                depth(i),
+            )
+        })
+        .collect()
+}
+
+// 按 X_c = R·X_w + t 投影到像素坐标
+pub fn project_points(
+    camera_matrix: &Matrix3<f64>,
+    landmarks: &[Vector3<f64>],
+    r: &Matrix3<f64>,
+    t: &Vector3<f64>,
+) -> Vec<Vector2<f64>> {
+    landmarks
+        .iter()
+        .map(|p| {
+            let p = camera_matrix * (r * p + t);
+            p.xy() / p.z
+        })
+        .collect()
+}
@@ -60,7 +60,7 @@ impl TrackedViewer {
             //println!("point {}", i);
             let mut prev: Option<track::TrackedPoint> = None;
             let mut color_ratio = 1.0;
-            'a: for j in 0..tracked.frames_count() {
+            for j in 0..tracked.frames_count() {
                 let color = opencv::core::Scalar::new(
                     256.0 * (1.0 - color_ratio),
                     256.0 * color_ratio,
@@ -102,8 +102,6 @@ impl TrackedViewer {
                     prev = Some(cur_point);
 
                     color_ratio *= 0.95;
-                } else {
-                    break 'a;
                 }
             }
         }